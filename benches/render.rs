@@ -0,0 +1,114 @@
+//! Renders the graph of a synthetic lock file with 10,000 crates.
+//!
+//! Run with `cargo bench`. Fails if getting and rendering the graph takes a second or more.
+
+use cargo_deps::{get_dep_graph, render_dep_graph, Config};
+use std::{
+    env,
+    fmt::Write,
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const CRATES: usize = 10_000;
+const DEPS_PER_CRATE: usize = 5;
+const LIMIT: Duration = Duration::from_secs(1);
+
+fn main() {
+    let dir = env::temp_dir().join(format!("cargo-deps-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    write_project(&dir);
+
+    let mut failed = false;
+    for &transitive_deps in &[true, false] {
+        let cfg = Config {
+            manifest_path: dir.join("Cargo.toml").to_str().unwrap().into(),
+            transitive_deps,
+            ..Config::default()
+        };
+
+        let start = Instant::now();
+        let graph = get_dep_graph(cfg).unwrap();
+        let edges = graph.edges.len();
+        let out = render_dep_graph(graph).unwrap();
+        let elapsed = start.elapsed();
+
+        println!(
+            "{} crates, transitive deps {}: {} edges, {} bytes of DOT in {:?}",
+            CRATES,
+            if transitive_deps { "shown" } else { "filtered" },
+            edges,
+            out.len(),
+            elapsed
+        );
+        failed |= elapsed >= LIMIT;
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(!failed, "rendering took {:?} or more", LIMIT);
+}
+
+// Writes a root manifest and a lock file in which each crate depends on a few crates with lower
+// indices, chosen with a simple pseudo-random generator so that runs are reproducible. The root
+// crate depends on every crate that nothing else depends on.
+fn write_project(dir: &Path) {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move |bound: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % bound as u64) as usize
+    };
+
+    let mut deps = vec![vec![]; CRATES];
+    let mut has_parent = vec![false; CRATES];
+    for (i, deps) in deps.iter_mut().enumerate().skip(1) {
+        for _ in 0..DEPS_PER_CRATE.min(i) {
+            let dep = next(i);
+            if !deps.contains(&dep) {
+                deps.push(dep);
+                has_parent[dep] = true;
+            }
+        }
+    }
+    let root_deps = (0..CRATES).filter(|&i| !has_parent[i]).collect::<Vec<_>>();
+
+    let mut manifest =
+        String::from("[package]\nname = \"bench-root\"\nversion = \"0.1.0\"\n\n[dependencies]\n");
+    for i in root_deps.iter() {
+        writeln!(manifest, "crate-{} = \"1\"", i).unwrap();
+    }
+
+    let mut lock = String::from("version = 3\n");
+    write_package(&mut lock, "bench-root", "0.1.0", false, &root_deps);
+    for (i, deps) in deps.iter().enumerate() {
+        write_package(&mut lock, &format!("crate-{}", i), "1.0.0", true, deps);
+    }
+
+    fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    fs::write(dir.join("Cargo.lock"), lock).unwrap();
+}
+
+fn write_package(lock: &mut String, name: &str, ver: &str, registry: bool, deps: &[usize]) {
+    writeln!(
+        lock,
+        "\n[[package]]\nname = \"{}\"\nversion = \"{}\"",
+        name, ver
+    )
+    .unwrap();
+    if registry {
+        writeln!(
+            lock,
+            "source = \"registry+https://github.com/rust-lang/crates.io-index\""
+        )
+        .unwrap();
+    }
+    if !deps.is_empty() {
+        writeln!(lock, "dependencies = [").unwrap();
+        for dep in deps {
+            writeln!(lock, " \"crate-{}\",", dep).unwrap();
+        }
+        writeln!(lock, "]").unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+use crate::graph::Node;
+
+/// A set of nodes, stored as one bit per node.
+#[derive(Clone, Debug, Default)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    /// Creates an empty set that can hold the nodes `0..len`.
+    pub fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    pub fn insert(&mut self, node: Node) {
+        self.words[node / 64] |= 1 << (node % 64);
+    }
+
+    pub fn contains(&self, node: Node) -> bool {
+        self.words
+            .get(node / 64)
+            .is_some_and(|word| word & (1 << (node % 64)) != 0)
+    }
+
+    pub fn union_with(&mut self, other: &Self) {
+        for (word, other) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= other;
+        }
+    }
+}
+
+/// The transitive closure of a graph: the set of nodes that can be reached from each node by
+/// following one or more edges.
+///
+/// The sets are computed once, bottom-up over the strongly connected components of the graph.
+/// Nodes in the same component reach the same nodes, so they share a set.
+#[derive(Clone, Debug)]
+pub struct TransitiveClosure {
+    component_of: Vec<usize>,
+    reach: Vec<BitSet>,
+}
+
+impl TransitiveClosure {
+    /// Computes the closure of the graph given by `children`. `components` must be its strongly
+    /// connected components in topological order, as found by
+    /// `cycles::strongly_connected_components`.
+    pub fn new(children: &[Vec<Node>], components: &[Vec<Node>]) -> Self {
+        let mut component_of = vec![0; children.len()];
+        for (i, component) in components.iter().enumerate() {
+            for &n in component {
+                component_of[n] = i;
+            }
+        }
+
+        // Every edge leads to the same or a later component, so visiting the components in reverse
+        // means the sets of the children's components are always complete.
+        let mut reach = vec![BitSet::default(); components.len()];
+        for (i, component) in components.iter().enumerate().rev() {
+            let mut set = BitSet::new(children.len());
+            for &n in component {
+                for &child in children[n].iter() {
+                    set.insert(child);
+                    if component_of[child] != i {
+                        set.union_with(&reach[component_of[child]]);
+                    }
+                }
+            }
+            reach[i] = set;
+        }
+
+        Self {
+            component_of,
+            reach,
+        }
+    }
+
+    /// Checks whether `to` can be reached from `from` by following one or more edges.
+    pub fn reaches(&self, from: Node, to: Node) -> bool {
+        self.reach[self.component_of[from]].contains(to)
+    }
+}
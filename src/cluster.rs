@@ -0,0 +1,258 @@
+use crate::{
+    dep::SourceKind,
+    error::{Error, Result},
+    graph::{DepGraph, Node},
+};
+use std::{collections::BTreeMap, path::Path, str::FromStr};
+use toml::Value;
+
+/// Colors given to clusters without a color of their own, in order.
+const PALETTE: &[&str] = &[
+    "darkgreen",
+    "blue4",
+    "darkorange3",
+    "purple4",
+    "firebrick",
+    "deepskyblue4",
+    "goldenrod4",
+    "deeppink4",
+];
+
+/// A named group of crates, rendered as its own cluster.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cluster {
+    /// The label of the cluster.
+    pub name: String,
+    /// The crates in the cluster, as crate selectors such as `tokio-*` or `re:^serde`.
+    pub crates: Vec<String>,
+    /// The color of the cluster, or `None` to pick one from a palette.
+    pub color: Option<String>,
+}
+
+impl Cluster {
+    /// Parses a cluster given as `NAME=SELECTOR`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec.find('=') {
+            Some(i) if i > 0 && i + 1 < spec.len() => Ok(Self {
+                name: spec[..i].into(),
+                crates: vec![spec[i + 1..].into()],
+                color: None,
+            }),
+            _ => Err(Error::InvalidConfig(format!(
+                "Invalid cluster '{}': expected NAME=CRATES",
+                spec
+            ))),
+        }
+    }
+}
+
+/// How to group crates into clusters automatically.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClusterBy {
+    /// Group each workspace member with the crates that only it depends on.
+    Member,
+    /// Group crates by where they come from: crates.io, another registry, git or a path.
+    Source,
+    /// Group crates whose names start with the same word, e.g. `tokio` and `tokio-util`.
+    Prefix,
+}
+
+impl FromStr for ClusterBy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "member" => Ok(Self::Member),
+            "source" => Ok(Self::Source),
+            "prefix" => Ok(Self::Prefix),
+            _ => Err(Error::InvalidConfig(format!(
+                "Invalid cluster mode '{}': expected member, source or prefix",
+                s
+            ))),
+        }
+    }
+}
+
+/// Adds clusters to a list, merging clusters with the same name. The clusters keep the order in
+/// which their names first appear.
+pub fn merge(clusters: &mut Vec<Cluster>, new_clusters: Vec<Cluster>) {
+    for cluster in new_clusters {
+        match clusters.iter_mut().find(|c| c.name == cluster.name) {
+            Some(existing) => {
+                existing.crates.extend(cluster.crates);
+                if cluster.color.is_some() {
+                    existing.color = cluster.color;
+                }
+            }
+            None => clusters.push(cluster),
+        }
+    }
+}
+
+/// Reads the clusters declared in a manifest as `[[package.metadata.deps.cluster]]` or
+/// `[[workspace.metadata.deps.cluster]]` tables, each with a `name`, a `crates` array and an
+/// optional `color`.
+pub fn from_manifest(manifest_path: &Path, manifest_toml: &Value) -> Result<Vec<Cluster>> {
+    let mut clusters = vec![];
+
+    for table_name in &["package", "workspace"] {
+        let key = format!("{}.metadata.deps.cluster", table_name);
+        let tables = match manifest_toml
+            .get(table_name)
+            .and_then(|table| table.get("metadata"))
+            .and_then(|metadata| metadata.get("deps"))
+            .and_then(|deps| deps.get("cluster"))
+        {
+            Some(Value::Array(tables)) => tables,
+            Some(_) => {
+                return Err(Error::invalid_key(
+                    manifest_path,
+                    &key,
+                    "expected an array of tables",
+                ))
+            }
+            None => continue,
+        };
+
+        for (i, table) in tables.iter().enumerate() {
+            let key = format!("{}[{}]", key, i);
+            let string = |field: &str| match table.get(field) {
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(_) => Err(Error::invalid_key(
+                    manifest_path,
+                    &format!("{}.{}", key, field),
+                    "expected a string",
+                )),
+                None => Ok(None),
+            };
+
+            let name = string("name")?.ok_or_else(|| {
+                Error::invalid_key(manifest_path, &format!("{}.name", key), "missing field")
+            })?;
+            let crates = match table.get("crates") {
+                Some(Value::Array(crates)) => crates
+                    .iter()
+                    .map(|krate| krate.as_str().map(String::from))
+                    .collect::<Option<Vec<_>>>(),
+                _ => None,
+            }
+            .ok_or_else(|| {
+                Error::invalid_key(
+                    manifest_path,
+                    &format!("{}.crates", key),
+                    "expected an array of strings",
+                )
+            })?;
+
+            merge(
+                &mut clusters,
+                vec![Cluster {
+                    name,
+                    crates,
+                    color: string("color")?,
+                }],
+            );
+        }
+    }
+
+    Ok(clusters)
+}
+
+/// The default color of the cluster at `index`.
+pub fn palette_color(index: usize) -> &'static str {
+    PALETTE[index % PALETTE.len()]
+}
+
+impl DepGraph {
+    /// Groups the visible nodes that are not in a cluster yet, returning the label and nodes of each
+    /// group in order.
+    pub fn auto_clusters(&self, by: ClusterBy, clustered: &[bool]) -> Vec<(String, Vec<Node>)> {
+        match by {
+            ClusterBy::Member => {
+                let owners = self.exclusive_roots();
+                let mut groups: BTreeMap<_, Vec<Node>> = BTreeMap::new();
+                for n in self.free_nodes(clustered) {
+                    if let Some(root) = owners[n] {
+                        let root = &self.nodes[root];
+                        groups
+                            .entry((root.name.clone(), root.ver.clone()))
+                            .or_default()
+                            .push(n);
+                    }
+                }
+                groups
+                    .into_iter()
+                    .map(|((name, _), nodes)| (name, nodes))
+                    .collect()
+            }
+            ClusterBy::Source => {
+                let mut groups: BTreeMap<_, Vec<Node>> = BTreeMap::new();
+                for n in self.free_nodes(clustered) {
+                    let kind = match self.nodes[n].source_kind() {
+                        SourceKind::CratesIo => (0, "crates.io"),
+                        SourceKind::Registry => (1, "registry"),
+                        SourceKind::Git => (2, "git"),
+                        SourceKind::Path => (3, "path"),
+                    };
+                    groups.entry(kind).or_default().push(n);
+                }
+                groups
+                    .into_iter()
+                    .map(|((_, label), nodes)| (label.into(), nodes))
+                    .collect()
+            }
+            ClusterBy::Prefix => {
+                // The prefix is the first word of the name. A prefix shared by a single crate is
+                // not a family.
+                let mut groups: BTreeMap<_, Vec<Node>> = BTreeMap::new();
+                for n in self.free_nodes(clustered) {
+                    let name = &self.nodes[n].name;
+                    let prefix = name.split(['-', '_']).next().unwrap_or(name);
+                    groups.entry(format!("{}*", prefix)).or_default().push(n);
+                }
+                groups
+                    .into_iter()
+                    .filter(|(_, nodes)| nodes.len() > 1)
+                    .collect()
+            }
+        }
+    }
+
+    // Gets the visible nodes that are not in a cluster. Unlike named clusters, automatic ones do
+    // not bring back nodes below the maximum depth or orphans.
+    fn free_nodes<'a>(&'a self, clustered: &'a [bool]) -> impl Iterator<Item = Node> + 'a {
+        (0..self.nodes.len()).filter(move |&n| {
+            let dep = &self.nodes[n];
+            !clustered[n] && !dep.hidden && self.within_limits(dep)
+        })
+    }
+
+    // Finds, for each node, the only visible root crate that it can be reached from, if there is
+    // exactly one.
+    fn exclusive_roots(&self) -> Vec<Option<Node>> {
+        let mut children = vec![vec![]; self.nodes.len()];
+        for edge in self.edges.iter() {
+            children[edge.0].push(edge.1);
+        }
+
+        // `None` if no root reaches the node, `Some(None)` if several do.
+        let mut owners: Vec<Option<Option<Node>>> = vec![None; self.nodes.len()];
+        for root in (0..self.nodes.len()).filter(|&n| self.is_root(n) && !self.nodes[n].hidden) {
+            let mut visited = vec![false; self.nodes.len()];
+            let mut stack = vec![root];
+            while let Some(n) = stack.pop() {
+                if visited[n] {
+                    continue;
+                }
+                visited[n] = true;
+                owners[n] = match owners[n] {
+                    None => Some(Some(root)),
+                    Some(_) => Some(None),
+                };
+                stack.extend(children[n].iter().copied());
+            }
+        }
+
+        owners.into_iter().map(Option::flatten).collect()
+    }
+}
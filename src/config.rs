@@ -1,4 +1,7 @@
-use crate::error::Result;
+use crate::{
+    cluster::{self, Cluster, ClusterBy},
+    error::{Error, Result},
+};
 use clap::ArgMatches;
 use std::str::FromStr;
 
@@ -7,19 +10,52 @@ use std::str::FromStr;
 /// Create this object with `Default::default()` for the configuration equivalent to running without
 /// any command-line arguments.
 ///
-/// Please refer to the help menu for information about each option.
+/// Please refer to the help menu for information about each option. Options that take crates
+/// accept the same selectors as the command line, e.g. `tokio-*`, `re:^aws-` or `serde@1`.
 #[derive(Clone, Debug)]
 #[allow(missing_docs)]
 pub struct Config {
     pub depth: Option<usize>,
     pub dot_file: Option<String>,
+    /// Features to activate, as `feature` or `crate/feature`.
+    pub features: Option<Vec<String>>,
+    pub all_features: bool,
+    pub no_default_features: bool,
     pub filter: Option<Vec<String>>,
+    /// Keep only these crates and the crates they depend on or that depend on them.
+    pub focus: Option<Vec<String>>,
+    /// Remove these crates and the crates that are then no longer reachable from a root crate.
+    pub prune: Option<Vec<String>>,
+    /// Remove these crates, but not the crates they depend on.
+    pub exclude: Option<Vec<String>>,
     pub include_orphans: bool,
     pub include_versions: bool,
+    /// Render the crates that depend on this crate, with edges pointing to the dependents.
+    pub invert: Option<String>,
+    /// Render the dominator tree instead of the dependencies.
+    pub dominators: bool,
     /// Default: "Cargo.toml".
     pub manifest_path: String,
+    /// The lock file to read. Default: the Cargo.lock next to the manifest or in a parent
+    /// directory.
+    pub lock_path: Option<String>,
+    /// Read the manifests and the lock file as they were at this git revision of the local
+    /// repository, e.g. `HEAD~1` or `main`, instead of from the working tree. Default: none.
+    pub rev: Option<String>,
+    /// The Cargo home to find the manifests of registry packages in. Default: `$CARGO_HOME` or
+    /// `~/.cargo`.
+    pub cargo_home: Option<String>,
     pub subgraph: Option<Vec<String>>,
     pub subgraph_name: Option<String>,
+    /// Named groups of crates, each rendered as its own cluster. Clusters declared in the manifest
+    /// come first. Default: none.
+    pub clusters: Vec<Cluster>,
+    /// Group the crates that are not in a named cluster automatically. Default: none.
+    pub cluster_by: Option<ClusterBy>,
+    /// Target triple to show the graph for. Default: all platforms.
+    pub target: Option<String>,
+    /// Custom cfg flags, as `name` or `key="value"`, used when evaluating `cfg(..)` for `target`.
+    pub cfgs: Option<Vec<String>>,
 
     /// Default: true.
     pub regular_deps: bool,
@@ -31,6 +67,8 @@ pub struct Config {
     pub optional_deps: bool,
     /// Default: true.
     pub transitive_deps: bool,
+    /// Render graphs with cycles instead of failing. Default: false.
+    pub allow_cycles: bool,
 }
 
 impl Default for Config {
@@ -38,18 +76,34 @@ impl Default for Config {
         Self {
             depth: None,
             dot_file: None,
+            features: None,
+            all_features: false,
+            no_default_features: false,
             filter: None,
+            focus: None,
+            prune: None,
+            exclude: None,
             include_orphans: false,
             include_versions: false,
+            invert: None,
+            dominators: false,
             manifest_path: "Cargo.toml".into(),
+            lock_path: None,
+            rev: None,
+            cargo_home: None,
             subgraph: None,
             subgraph_name: None,
+            clusters: vec![],
+            cluster_by: None,
+            target: None,
+            cfgs: None,
 
             regular_deps: true,
             build_deps: false,
             dev_deps: false,
             optional_deps: false,
             transitive_deps: true,
+            allow_cycles: false,
         }
     }
 }
@@ -59,27 +113,69 @@ impl Config {
     pub fn from_matches(m: &ArgMatches) -> Result<Self> {
         let all_deps = m.is_present("all-deps");
 
+        // Repeating a cluster name adds crates to that cluster.
+        let mut clusters = vec![];
+        for spec in m.values_of("cluster").into_iter().flatten() {
+            cluster::merge(&mut clusters, vec![Cluster::parse(spec)?]);
+        }
+
         Ok(Self {
             depth: m
                 .value_of("depth")
-                .map(|depth| usize::from_str(depth).unwrap()),
+                .map(|depth| {
+                    usize::from_str(depth).map_err(|err| {
+                        Error::InvalidConfig(format!("Invalid depth '{}': {}", depth, err))
+                    })
+                })
+                .transpose()?,
             dot_file: m.value_of("dot-file").map(|s| s.into()),
+            // Features can be separated by commas or spaces.
+            features: m.values_of("features").map(|features| {
+                features
+                    .flat_map(|f| f.split(|c: char| c == ',' || c.is_whitespace()))
+                    .filter(|f| !f.is_empty())
+                    .map(|f| f.into())
+                    .collect()
+            }),
+            all_features: m.is_present("all-features"),
+            no_default_features: m.is_present("no-default-features"),
             filter: m
                 .values_of("filter")
                 .map(|deps| deps.map(|dep| dep.into()).collect()),
+            focus: m
+                .values_of("focus")
+                .map(|deps| deps.map(|dep| dep.into()).collect()),
+            prune: m
+                .values_of("prune")
+                .map(|deps| deps.map(|dep| dep.into()).collect()),
+            exclude: m
+                .values_of("exclude")
+                .map(|deps| deps.map(|dep| dep.into()).collect()),
             include_orphans: m.is_present("include-orphans"),
             include_versions: m.is_present("include-versions"),
+            invert: m.value_of("invert").map(|s| s.into()),
+            dominators: m.is_present("dominators"),
             manifest_path: m.value_of("manifest-path").unwrap_or("Cargo.toml").into(),
+            lock_path: None,
+            rev: m.value_of("rev").map(|s| s.into()),
+            cargo_home: None,
             subgraph: m
                 .values_of("subgraph")
                 .map(|deps| deps.map(|dep| dep.into()).collect()),
             subgraph_name: m.value_of("subgraph-name").map(|s| s.into()),
+            clusters,
+            cluster_by: m.value_of("cluster-by").map(str::parse).transpose()?,
+            target: m.value_of("target").map(|s| s.into()),
+            cfgs: m
+                .values_of("cfg")
+                .map(|cfgs| cfgs.map(|cfg| cfg.into()).collect()),
 
             regular_deps: !m.is_present("no-regular-deps"),
             build_deps: all_deps || m.is_present("build-deps"),
             dev_deps: all_deps || m.is_present("dev-deps"),
             optional_deps: all_deps || m.is_present("optional-deps"),
             transitive_deps: !m.is_present("no-transitive-deps"),
+            allow_cycles: m.is_present("allow-cycles"),
         })
     }
 }
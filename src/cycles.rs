@@ -0,0 +1,133 @@
+use crate::graph::Node;
+use std::collections::{HashSet, VecDeque};
+
+/// Finds the strongly connected components of the subgraph formed by `nodes`, following the edges
+/// in `children` that stay within `nodes`. The components are returned in topological order: no
+/// edge leads from a component to an earlier one.
+///
+/// This is Tarjan's algorithm, written iteratively so that long dependency chains cannot overflow
+/// the stack.
+pub fn strongly_connected_components(children: &[Vec<Node>], nodes: &[Node]) -> Vec<Vec<Node>> {
+    const UNVISITED: usize = usize::MAX;
+
+    let in_subgraph = nodes.iter().copied().collect::<HashSet<_>>();
+    let mut index = vec![UNVISITED; children.len()];
+    let mut lowlink = vec![0; children.len()];
+    let mut on_stack = vec![false; children.len()];
+    let mut stack = vec![];
+    let mut components = vec![];
+    let mut next_index = 0;
+
+    for &start in nodes {
+        if index[start] != UNVISITED {
+            continue;
+        }
+
+        // Each frame is a node and the position of the next child to visit.
+        let mut frames = vec![(start, 0)];
+        index[start] = next_index;
+        lowlink[start] = next_index;
+        next_index += 1;
+        stack.push(start);
+        on_stack[start] = true;
+
+        while let Some(&mut (n, ref mut pos)) = frames.last_mut() {
+            if let Some(&child) = children[n].get(*pos) {
+                *pos += 1;
+                if !in_subgraph.contains(&child) {
+                    continue;
+                }
+
+                if index[child] == UNVISITED {
+                    index[child] = next_index;
+                    lowlink[child] = next_index;
+                    next_index += 1;
+                    stack.push(child);
+                    on_stack[child] = true;
+                    frames.push((child, 0));
+                } else if on_stack[child] {
+                    lowlink[n] = lowlink[n].min(index[child]);
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                lowlink[parent] = lowlink[parent].min(lowlink[n]);
+            }
+
+            if lowlink[n] == index[n] {
+                let mut component = vec![];
+                while let Some(m) = stack.pop() {
+                    on_stack[m] = false;
+                    component.push(m);
+                    if m == n {
+                        break;
+                    }
+                }
+                component.reverse();
+                components.push(component);
+            }
+        }
+    }
+
+    // Tarjan's algorithm finds the components in reverse topological order.
+    components.reverse();
+    components
+}
+
+/// Lists cycles within a strongly connected component such that every edge of the component is on
+/// at least one of them. Each cycle starts and ends with the same node.
+pub fn covering_cycles(children: &[Vec<Node>], component: &[Node]) -> Vec<Vec<Node>> {
+    let in_component = component.iter().copied().collect::<HashSet<_>>();
+    let mut covered = HashSet::new();
+    let mut cycles = vec![];
+
+    for &n in component {
+        for &child in children[n].iter() {
+            if !in_component.contains(&child) || covered.contains(&(n, child)) {
+                continue;
+            }
+
+            // Close the cycle with the shortest path back from the child.
+            let mut cycle = vec![n];
+            cycle.extend(shortest_path(children, &in_component, child, n));
+            for pair in cycle.windows(2) {
+                covered.insert((pair[0], pair[1]));
+            }
+            cycles.push(cycle);
+        }
+    }
+
+    cycles
+}
+
+// Finds the shortest path from `from` to `to` with a breadth-first search that stays within
+// `nodes`. Both nodes must be in the same strongly connected component, so a path always exists.
+fn shortest_path(children: &[Vec<Node>], nodes: &HashSet<Node>, from: Node, to: Node) -> Vec<Node> {
+    let mut prev = vec![None; children.len()];
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+    prev[from] = Some(from);
+
+    while let Some(n) = queue.pop_front() {
+        if n == to {
+            break;
+        }
+        for &child in children[n].iter() {
+            if nodes.contains(&child) && prev[child].is_none() {
+                prev[child] = Some(n);
+                queue.push_back(child);
+            }
+        }
+    }
+
+    let mut path = vec![to];
+    let mut n = to;
+    while n != from {
+        n = prev[n].unwrap();
+        path.push(n);
+    }
+    path.reverse();
+    path
+}
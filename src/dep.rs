@@ -1,5 +1,8 @@
 use crate::graph::{DepGraph, Node};
-use std::io::{Result, Write};
+use std::{
+    fmt,
+    io::{Result, Write},
+};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DepKind {
@@ -10,6 +13,35 @@ pub enum DepKind {
     Unknown,
 }
 
+impl fmt::Display for DepKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Regular => "regular",
+            Self::Build => "build",
+            Self::Dev => "dev",
+            Self::Optional => "optional",
+            Self::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Where a package comes from, based on its `source` in the lock file.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SourceKind {
+    CratesIo,
+    /// An alternate registry.
+    Registry,
+    Git,
+    /// A local path. Includes the root crates.
+    Path,
+}
+
+const CRATES_IO_SOURCES: &[&str] = &[
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct RootCrate {
     pub name: String,
@@ -20,8 +52,16 @@ pub struct RootCrate {
 pub struct ResolvedDep {
     pub name: String,
     pub ver: String,
+    /// The source from the lock file, if any.
+    pub source: Option<String>,
     pub force_write_ver: bool,
+    pub force_write_source: bool,
     pub depth: Option<usize>,
+    /// Hidden nodes and their edges are not rendered.
+    pub hidden: bool,
+    /// The number of crates that only this crate leads to, shown on the node when rendering the
+    /// dominator tree.
+    pub dominated: Option<usize>,
 
     pub is_regular: bool,
     pub is_build: bool,
@@ -33,12 +73,16 @@ pub struct ResolvedDep {
 }
 
 impl ResolvedDep {
-    pub fn new(name: String, ver: String) -> Self {
+    pub fn new(name: String, ver: String, source: Option<String>) -> Self {
         Self {
             name,
             ver,
+            source,
             force_write_ver: false,
+            force_write_source: false,
             depth: None,
+            hidden: false,
+            dominated: None,
 
             is_regular: false,
             is_build: false,
@@ -64,25 +108,86 @@ impl ResolvedDep {
         }
     }
 
-    pub fn label<W: Write>(&self, w: &mut W, dg: &DepGraph) -> Result<()> {
-        let name = if self.force_write_ver || dg.cfg.include_versions {
+    pub fn source_kind(&self) -> SourceKind {
+        match self.source {
+            None => SourceKind::Path,
+            Some(ref source) if source.starts_with("git+") => SourceKind::Git,
+            Some(ref source) if CRATES_IO_SOURCES.contains(&source.as_str()) => {
+                SourceKind::CratesIo
+            }
+            Some(_) => SourceKind::Registry,
+        }
+    }
+
+    // Describes the source for the label, e.g. `git: github.com/serde-rs/serde#1234567`.
+    fn source_label(&self) -> String {
+        let source = match self.source {
+            Some(ref source) => source,
+            None => return "path".into(),
+        };
+        let (kind, url) = match source.find('+') {
+            Some(i) => (&source[..i], &source[i + 1..]),
+            None => ("", source.as_str()),
+        };
+        let url = url
+            .trim_start_matches("https://")
+            .trim_start_matches("http://");
+
+        match self.source_kind() {
+            SourceKind::Git => {
+                // Drop the query, e.g. `?branch=main`, and shorten the commit hash.
+                let (repo, rev) = match url.find('#') {
+                    Some(i) => (&url[..i], &url[i + 1..]),
+                    None => (url, ""),
+                };
+                let repo = repo.split('?').next().unwrap_or(repo);
+                let rev = &rev[..rev.len().min(7)];
+                format!("{}: {}#{}", kind, repo, rev)
+            }
+            SourceKind::CratesIo => "crates.io".into(),
+            _ => format!("registry: {}", url),
+        }
+    }
+
+    pub fn label<W: Write>(&self, w: &mut W, dg: &DepGraph, node: Node) -> Result<()> {
+        let mut name = if self.force_write_ver || dg.cfg.include_versions {
             format!("{} v{}", self.name, self.ver)
         } else {
             self.name.clone()
         };
+        if self.force_write_source {
+            name = format!("{}\\n({})", name, self.source_label());
+        }
+        match self.dominated {
+            Some(0) | None => (),
+            Some(1) => name = format!("{}\\ngates 1 crate", name),
+            Some(dominated) => name = format!("{}\\ngates {} crates", name, dominated),
+        }
 
-        let shape = if dg.root_deps_map.contains_key(&self.name) {
-            ", shape=box"
-        } else {
-            ""
-        };
+        let mut attrs = vec![format!("label=\"{}\"", name)];
 
         match self.kind() {
-            DepKind::Regular => writeln!(w, " [label=\"{}\"{}];", name, shape),
-            DepKind::Build => writeln!(w, " [label=\"{}\", color=purple];", name),
-            DepKind::Dev => writeln!(w, " [label=\"{}\", color=blue];", name),
-            DepKind::Optional => writeln!(w, " [label=\"{}\", color=red];", name),
-            _ => writeln!(w, " [label=\"{}\", color=orange];", name),
+            DepKind::Regular => (),
+            DepKind::Build => attrs.push("color=purple".into()),
+            DepKind::Dev => attrs.push("color=blue".into()),
+            DepKind::Optional => attrs.push("color=red".into()),
+            _ => attrs.push("color=orange".into()),
+        }
+
+        // Root crates are boxes. Other crates are styled by where they come from.
+        if dg.is_root(node) {
+            if let DepKind::Regular = self.kind() {
+                attrs.push("shape=box".into());
+            }
+        } else {
+            match self.source_kind() {
+                SourceKind::CratesIo => (),
+                SourceKind::Registry => attrs.push("style=bold".into()),
+                SourceKind::Git => attrs.push("style=filled, fillcolor=lightyellow".into()),
+                SourceKind::Path => attrs.push("shape=box, style=rounded".into()),
+            }
         }
+
+        writeln!(w, " [{}];", attrs.join(", "))
     }
 }
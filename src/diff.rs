@@ -0,0 +1,421 @@
+use crate::{
+    error::{Error, Result},
+    graph::{DepGraph, Node},
+    util,
+};
+use semver::Version;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    str::FromStr,
+};
+
+/// How to write the report of `DepGraph::write_diff`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiffFormat {
+    /// A plain text summary.
+    Text,
+    /// A Markdown summary, e.g. for a pull request comment.
+    Markdown,
+    /// A graph of both versions in dot format, with the changes colored.
+    Dot,
+}
+
+impl FromStr for DiffFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "markdown" => Ok(Self::Markdown),
+            "dot" => Ok(Self::Dot),
+            _ => Err(Error::InvalidConfig(format!(
+                "Invalid format '{}': expected text, markdown or dot",
+                s
+            ))),
+        }
+    }
+}
+
+/// A crate whose version changed between two graphs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionChange {
+    /// The name of the crate.
+    pub name: String,
+    /// The version in the old graph.
+    pub old: String,
+    /// The version in the new graph.
+    pub new: String,
+}
+
+/// The differences between two dependency graphs, as found by `DepGraph::diff`. Crates are
+/// identified by name and version, and edges by the names of the crates they join, so that an
+/// upgrade does not count as new edges. Everything is sorted by name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GraphDiff {
+    /// The crates that are only in the new graph, as names and versions.
+    pub added: Vec<(String, String)>,
+    /// The crates that are only in the old graph, as names and versions.
+    pub removed: Vec<(String, String)>,
+    /// The crates that went up a version.
+    pub upgraded: Vec<VersionChange>,
+    /// The crates that went down a version.
+    pub downgraded: Vec<VersionChange>,
+    /// The dependencies that are only in the new graph, as the names of the parent and the child.
+    pub added_edges: Vec<(String, String)>,
+    /// The dependencies that are only in the old graph, as the names of the parent and the child.
+    pub removed_edges: Vec<(String, String)>,
+}
+
+impl GraphDiff {
+    /// Checks whether the graphs are the same.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl DepGraph {
+    /// Finds what changed from an older version of the graph to this one.
+    ///
+    /// When a crate has several versions, each version that is in only one graph is paired with a
+    /// semver-compatible one in the other graph if there is one, e.g. `1.0.1` with `1.0.3` but not
+    /// with `2.0.0`. A version left over on each side is paired as well.
+    pub fn diff(&self, old: &DepGraph) -> GraphDiff {
+        let mut diff = GraphDiff::default();
+        let old_versions = old.versions_by_name();
+        let new_versions = self.versions_by_name();
+        let names = old_versions
+            .keys()
+            .chain(new_versions.keys())
+            .collect::<BTreeSet<_>>();
+
+        for &name in names.iter() {
+            let empty = BTreeSet::new();
+            let old_vers = old_versions.get(name).unwrap_or(&empty);
+            let new_vers = new_versions.get(name).unwrap_or(&empty);
+            let mut removed = old_vers.difference(new_vers).cloned().collect::<Vec<_>>();
+            let mut added = new_vers.difference(old_vers).cloned().collect::<Vec<_>>();
+
+            let mut changes = vec![];
+            added.retain(|new| {
+                let new_ver = Version::parse(new).ok();
+                let compatible = removed.iter().position(|old| {
+                    match (Version::parse(old).ok(), new_ver.as_ref()) {
+                        (Some(old), Some(new)) => util::is_compatible(&old, new),
+                        _ => false,
+                    }
+                });
+                match compatible {
+                    Some(i) => {
+                        changes.push((removed.remove(i), new.clone()));
+                        false
+                    }
+                    None => true,
+                }
+            });
+            if removed.len() == 1 && added.len() == 1 {
+                changes.push((removed.remove(0), added.remove(0)));
+            }
+
+            for (old, new) in changes {
+                let upgraded = match (Version::parse(&old), Version::parse(&new)) {
+                    (Ok(old), Ok(new)) => new > old,
+                    _ => new > old,
+                };
+                let change = VersionChange {
+                    name: name.clone(),
+                    old,
+                    new,
+                };
+                if upgraded {
+                    diff.upgraded.push(change);
+                } else {
+                    diff.downgraded.push(change);
+                }
+            }
+            diff.removed
+                .extend(removed.into_iter().map(|ver| (name.clone(), ver)));
+            diff.added
+                .extend(added.into_iter().map(|ver| (name.clone(), ver)));
+        }
+
+        let old_edges = old.edges_by_name();
+        let new_edges = self.edges_by_name();
+        diff.added_edges = new_edges.difference(&old_edges).cloned().collect();
+        diff.removed_edges = old_edges.difference(&new_edges).cloned().collect();
+
+        diff
+    }
+
+    /// Writes the differences from an older version of the graph to this one. The dot format draws
+    /// a node for each crate name in either graph, with added crates and dependencies in green,
+    /// removed ones in red and version changes in orange.
+    pub fn write_diff<W: Write>(
+        &self,
+        old: &DepGraph,
+        diff: &GraphDiff,
+        format: DiffFormat,
+        w: &mut W,
+    ) -> Result<()> {
+        match format {
+            DiffFormat::Text => write_text(diff, w),
+            DiffFormat::Markdown => write_markdown(diff, w),
+            DiffFormat::Dot => self.write_diff_dot(old, diff, w),
+        }
+    }
+
+    fn write_diff_dot<W: Write>(&self, old: &DepGraph, diff: &GraphDiff, w: &mut W) -> Result<()> {
+        let (old_versions, new_versions) = (old.versions_by_name(), self.versions_by_name());
+        let names = old_versions
+            .keys()
+            .chain(new_versions.keys())
+            .collect::<BTreeSet<_>>();
+        let ids = names
+            .iter()
+            .enumerate()
+            .map(|(i, &name)| (name, i))
+            .collect::<BTreeMap<_, _>>();
+        let root_names = |dg: &'_ DepGraph| {
+            (0..dg.nodes.len())
+                .filter(|&n| dg.is_root(n))
+                .map(|n| dg.nodes[n].name.clone())
+                .collect::<BTreeSet<_>>()
+        };
+        let roots = root_names(self)
+            .union(&root_names(old))
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        writeln!(w, "digraph dependencies {{")?;
+        for &name in names.iter() {
+            let versions = |changes: &[VersionChange]| {
+                changes
+                    .iter()
+                    .filter(|change| change.name == *name)
+                    .map(|change| format!("{} -> {}", change.old, change.new))
+                    .collect::<Vec<_>>()
+            };
+            let changes = versions(&diff.upgraded)
+                .into_iter()
+                .chain(versions(&diff.downgraded))
+                .collect::<Vec<_>>();
+
+            let mut attrs = vec![];
+            if !new_versions.contains_key(name) {
+                attrs.push("color=red".into());
+                attrs.push("fontcolor=red".into());
+            } else if !old_versions.contains_key(name) {
+                attrs.push("color=green".into());
+                attrs.push("fontcolor=green".into());
+            } else if !changes.is_empty()
+                || diff
+                    .added
+                    .iter()
+                    .chain(diff.removed.iter())
+                    .any(|(n, _)| n == name)
+            {
+                // Some of the versions of the crate changed.
+                attrs.push("color=orange".into());
+            }
+            if roots.contains(name) {
+                attrs.push("shape=box".into());
+            }
+
+            let label = Some(name.to_string())
+                .into_iter()
+                .chain(changes)
+                .collect::<Vec<_>>()
+                .join("\\n");
+            attrs.insert(0, format!("label=\"{}\"", label));
+            writeln!(w, "\tn{} [{}];", ids[name], attrs.join(", "))?;
+        }
+        writeln!(w)?;
+
+        let edges = self
+            .edges_by_name()
+            .union(&old.edges_by_name())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        for edge in edges.iter() {
+            write!(w, "\tn{} -> n{}", ids[&edge.0], ids[&edge.1])?;
+            if diff.added_edges.contains(edge) {
+                writeln!(w, " [color=green];")?;
+            } else if diff.removed_edges.contains(edge) {
+                writeln!(w, " [color=red, style=dashed];")?;
+            } else {
+                writeln!(w, ";")?;
+            }
+        }
+        writeln!(w, "}}")?;
+
+        Ok(())
+    }
+
+    // Gets the versions of each visible crate, by name.
+    fn versions_by_name(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut versions: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        for dep in self.visible_nodes().map(|n| &self.nodes[n]) {
+            versions
+                .entry(dep.name.clone())
+                .or_default()
+                .insert(dep.ver.clone());
+        }
+        versions
+    }
+
+    // Gets the edges between visible crates, as the names of the crates they join.
+    fn edges_by_name(&self) -> BTreeSet<(String, String)> {
+        let visible = self.visible_nodes().collect::<BTreeSet<_>>();
+        self.edges
+            .iter()
+            .filter(|edge| visible.contains(&edge.0) && visible.contains(&edge.1))
+            .map(|edge| {
+                (
+                    self.nodes[edge.0].name.clone(),
+                    self.nodes[edge.1].name.clone(),
+                )
+            })
+            .collect()
+    }
+
+    // Gets the nodes that would be rendered.
+    fn visible_nodes(&self) -> impl Iterator<Item = Node> + '_ {
+        (0..self.nodes.len()).filter(move |&n| {
+            let dep = &self.nodes[n];
+            !dep.hidden && self.within_limits(dep)
+        })
+    }
+}
+
+fn write_text<W: Write>(diff: &GraphDiff, w: &mut W) -> Result<()> {
+    if diff.is_empty() {
+        return Ok(writeln!(w, "No changes")?);
+    }
+    writeln!(w, "{}", summary(diff))?;
+
+    let sections = [
+        ("Added crates", "+", crate_lines(&diff.added)),
+        ("Removed crates", "-", crate_lines(&diff.removed)),
+        ("Upgraded crates", "~", change_lines(&diff.upgraded)),
+        ("Downgraded crates", "~", change_lines(&diff.downgraded)),
+        ("Added dependencies", "+", edge_lines(&diff.added_edges)),
+        ("Removed dependencies", "-", edge_lines(&diff.removed_edges)),
+    ];
+    for (title, marker, lines) in sections.iter() {
+        if lines.is_empty() {
+            continue;
+        }
+        writeln!(w)?;
+        writeln!(w, "{}:", title)?;
+        for line in lines {
+            writeln!(w, "  {} {}", marker, line)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_markdown<W: Write>(diff: &GraphDiff, w: &mut W) -> Result<()> {
+    writeln!(w, "### Dependency changes")?;
+    writeln!(w)?;
+    if diff.is_empty() {
+        return Ok(writeln!(w, "No changes.")?);
+    }
+    writeln!(w, "{}.", summary(diff))?;
+
+    let mut rows = vec![];
+    for (name, ver) in diff.added.iter() {
+        rows.push(["Added", name, "", ver]);
+    }
+    for (name, ver) in diff.removed.iter() {
+        rows.push(["Removed", name, ver, ""]);
+    }
+    for change in diff.upgraded.iter() {
+        rows.push(["Upgraded", &change.name, &change.old, &change.new]);
+    }
+    for change in diff.downgraded.iter() {
+        rows.push(["Downgraded", &change.name, &change.old, &change.new]);
+    }
+    if !rows.is_empty() {
+        writeln!(w)?;
+        writeln!(w, "| Change | Crate | Old | New |")?;
+        writeln!(w, "|--------|-------|-----|-----|")?;
+        for [change, name, old, new] in rows {
+            let code = |s: &str| match s {
+                "" => String::new(),
+                s => format!("`{}`", s),
+            };
+            writeln!(w, "| {} | {} | {} | {} |", change, code(name), old, new)?;
+        }
+    }
+
+    for (title, edges) in &[
+        ("Added dependencies", &diff.added_edges),
+        ("Removed dependencies", &diff.removed_edges),
+    ] {
+        if edges.is_empty() {
+            continue;
+        }
+        writeln!(w)?;
+        writeln!(w, "**{}:**", title)?;
+        writeln!(w)?;
+        for line in edge_lines(edges) {
+            writeln!(w, "- `{}`", line)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Counts the changes, e.g. `2 crates added, 1 removed, 1 upgraded`.
+fn summary(diff: &GraphDiff) -> String {
+    let counts = [
+        (diff.added.len(), "added"),
+        (diff.removed.len(), "removed"),
+        (diff.upgraded.len(), "upgraded"),
+        (diff.downgraded.len(), "downgraded"),
+    ];
+    let crates = counts
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect::<Vec<_>>();
+    let edges = [
+        (diff.added_edges.len(), "added"),
+        (diff.removed_edges.len(), "removed"),
+    ]
+    .iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, what)| format!("{} {}", count, what))
+    .collect::<Vec<_>>();
+
+    let mut parts = vec![];
+    if !crates.is_empty() {
+        parts.push(format!("Crates: {}", crates.join(", ")));
+    }
+    if !edges.is_empty() {
+        parts.push(format!("Dependencies: {}", edges.join(", ")));
+    }
+    parts.join(". ")
+}
+
+fn crate_lines(crates: &[(String, String)]) -> Vec<String> {
+    crates
+        .iter()
+        .map(|(name, ver)| format!("{} v{}", name, ver))
+        .collect()
+}
+
+fn change_lines(changes: &[VersionChange]) -> Vec<String> {
+    changes
+        .iter()
+        .map(|change| format!("{} v{} -> v{}", change.name, change.old, change.new))
+        .collect()
+}
+
+fn edge_lines(edges: &[(String, String)]) -> Vec<String> {
+    edges
+        .iter()
+        .map(|(parent, child)| format!("{} -> {}", parent, child))
+        .collect()
+}
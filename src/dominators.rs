@@ -0,0 +1,153 @@
+use crate::graph::{DepGraph, Edge, Node};
+use std::collections::HashSet;
+
+/// The dominator tree of a dependency graph, as found by `DepGraph::dominator_tree`.
+///
+/// A crate dominates another if every path from the root crates to the other crate goes through
+/// it, so that removing it would remove the other crate as well. The root crates are joined under a
+/// virtual root, which dominates every crate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DominatorTree {
+    /// The immediate dominator of each node: the closest crate that dominates it. `None` for the
+    /// root crates, which only the virtual root dominates, and for the nodes that are not reached
+    /// from them.
+    pub idoms: Vec<Option<Node>>,
+    /// The number of crates that each node dominates, not counting itself, i.e. the number of
+    /// crates that would go away along with it.
+    pub dominated: Vec<usize>,
+}
+
+impl DominatorTree {
+    /// Checks whether `a` dominates `b`. Every node dominates itself.
+    pub fn dominates(&self, a: Node, b: Node) -> bool {
+        let mut n = Some(b);
+        while let Some(dominated) = n {
+            if dominated == a {
+                return true;
+            }
+            n = self.idoms[dominated];
+        }
+        false
+    }
+}
+
+impl DepGraph {
+    /// Computes the dominator tree of the visible crates, using the algorithm of Cooper, Harvey and
+    /// Kennedy, which also handles allowed cycles.
+    pub fn dominator_tree(&self) -> DominatorTree {
+        // The virtual root is numbered after the nodes, with an edge to each visible root crate.
+        let len = self.nodes.len();
+        let virtual_root = len;
+        let mut children = self.adjacency(false);
+        children.push(
+            (0..len)
+                .filter(|&n| self.is_root(n) && !self.nodes[n].hidden)
+                .collect(),
+        );
+        let mut parents = vec![vec![]; len + 1];
+        for (parent, children) in children.iter().enumerate() {
+            for &child in children.iter() {
+                parents[child].push(parent);
+            }
+        }
+
+        // Number the nodes in postorder, walking down from the virtual root.
+        let mut postorder = vec![];
+        let mut order = vec![None; len + 1];
+        let mut visited = vec![false; len + 1];
+        let mut stack = vec![(virtual_root, 0)];
+        visited[virtual_root] = true;
+        while let Some((n, i)) = stack.pop() {
+            match children[n].get(i) {
+                Some(&child) => {
+                    stack.push((n, i + 1));
+                    if !visited[child] {
+                        visited[child] = true;
+                        stack.push((child, 0));
+                    }
+                }
+                None => {
+                    order[n] = Some(postorder.len());
+                    postorder.push(n);
+                }
+            }
+        }
+
+        // Refine the dominators in reverse postorder until they no longer change.
+        let mut idoms = vec![None; len + 1];
+        idoms[virtual_root] = Some(virtual_root);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &n in postorder.iter().rev().skip(1) {
+                let mut processed = parents[n].iter().copied().filter(|&p| idoms[p].is_some());
+                let first = processed.next();
+                let idom = processed.fold(first, |idom, parent| {
+                    idom.map(|idom| intersect(&idoms, &order, idom, parent))
+                });
+                if idom != idoms[n] {
+                    idoms[n] = idom;
+                    changed = true;
+                }
+            }
+        }
+
+        // Each node adds itself and what it dominates to its immediate dominator, children first.
+        let mut dominated = vec![0; len + 1];
+        for &n in postorder.iter().filter(|&&n| n != virtual_root) {
+            if let Some(idom) = idoms[n] {
+                dominated[idom] += dominated[n] + 1;
+            }
+        }
+
+        idoms.truncate(len);
+        dominated.truncate(len);
+        DominatorTree {
+            idoms: idoms
+                .into_iter()
+                .map(|idom| idom.filter(|&idom| idom != virtual_root))
+                .collect(),
+            dominated,
+        }
+    }
+
+    /// Replaces the edges with those of the dominator tree, from each crate to the crates that it
+    /// immediately dominates, and records how many crates each crate dominates so that they are
+    /// shown on the nodes. Tree edges between crates that do not depend on each other directly are
+    /// drawn dotted.
+    pub fn retain_dominator_tree(&mut self) {
+        let tree = self.dominator_tree();
+        let edges = self.edges.iter().copied().collect::<HashSet<_>>();
+
+        self.edges = tree
+            .idoms
+            .iter()
+            .enumerate()
+            .filter_map(|(n, idom)| idom.map(|idom| Edge(idom, n)))
+            .collect();
+        self.edges.sort_unstable();
+        self.dominator_edges = self
+            .edges
+            .iter()
+            .copied()
+            .filter(|edge| !edges.contains(edge))
+            .collect();
+
+        for (dep, &dominated) in self.nodes.iter_mut().zip(tree.dominated.iter()) {
+            dep.dominated = Some(dominated);
+        }
+    }
+}
+
+// Finds the closest common dominator of two nodes, given the dominators found so far.
+fn intersect(idoms: &[Option<Node>], order: &[Option<usize>], mut a: Node, mut b: Node) -> Node {
+    while a != b {
+        while order[a] < order[b] {
+            a = idoms[a].unwrap();
+        }
+        while order[b] < order[a] {
+            b = idoms[b].unwrap();
+        }
+    }
+    a
+}
@@ -0,0 +1,229 @@
+use crate::{
+    error::Result,
+    graph::{DepGraph, Node},
+    ops,
+    paths::DepPaths,
+    unify::Unification,
+};
+use semver::Version;
+use std::{cmp::Reverse, collections::BTreeMap, io::Write};
+
+/// A crate that is in the graph in several versions, as found by `DepGraph::duplicates`.
+#[derive(Clone, Debug)]
+pub struct Duplicate {
+    /// The name of the crate.
+    pub name: String,
+    /// Each version, sorted by version.
+    pub versions: Vec<DuplicateVersion>,
+    /// The number of crates, including the other versions themselves, that would no longer be
+    /// needed if only one version was left, choosing the version that leaves the fewest.
+    pub extra_crates: usize,
+    /// What it would take to leave a single version.
+    pub unification: Unification,
+}
+
+/// One version of a duplicated crate.
+#[derive(Clone, Debug)]
+pub struct DuplicateVersion {
+    /// The node of this version.
+    pub node: Node,
+    /// The crates that depend on this version directly, sorted by name and version.
+    pub parents: Vec<Node>,
+    /// The version requirement with which each parent declares this version, if it was found.
+    pub requirements: Vec<Option<String>>,
+    /// The paths from the root crates to this version.
+    pub paths: DepPaths,
+}
+
+impl DepGraph {
+    /// Finds the crates that are in the graph in several versions, or from several sources, sorted
+    /// by how many extra crates the duplication costs. Up to `limit` paths from the root crates are
+    /// found for each version.
+    pub fn duplicates(&self, limit: usize) -> Result<Vec<Duplicate>> {
+        let mut nodes_by_name: BTreeMap<&str, Vec<Node>> = BTreeMap::new();
+        for (n, dep) in self.nodes.iter().enumerate() {
+            if !dep.hidden {
+                nodes_by_name.entry(&dep.name).or_default().push(n);
+            }
+        }
+
+        let mut duplicates = vec![];
+        for (name, mut nodes) in nodes_by_name.into_iter().filter(|(_, n)| n.len() > 1) {
+            nodes.sort_by_key(|&n| {
+                let dep = &self.nodes[n];
+                (Version::parse(&dep.ver).ok(), dep.source.clone())
+            });
+
+            let mut versions = vec![];
+            for &n in nodes.iter() {
+                let mut parents = self.nodes[n]
+                    .parents
+                    .iter()
+                    .copied()
+                    .filter(|&parent| !self.nodes[parent].hidden)
+                    .collect::<Vec<_>>();
+                parents.sort_by_key(|&parent| (&self.nodes[parent].name, &self.nodes[parent].ver));
+                parents.dedup();
+
+                let requirements = parents
+                    .iter()
+                    .map(|&parent| self.declared_req(parent, n))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let mut targets = vec![false; self.nodes.len()];
+                targets[n] = true;
+                versions.push(DuplicateVersion {
+                    node: n,
+                    parents,
+                    requirements,
+                    paths: self.paths_to_targets(&targets, limit)?,
+                });
+            }
+
+            duplicates.push(Duplicate {
+                name: name.into(),
+                unification: self.unification(&versions),
+                versions,
+                extra_crates: self.extra_crates(&nodes),
+            });
+        }
+
+        duplicates.sort_by_key(|duplicate| Reverse(duplicate.extra_crates));
+        Ok(duplicates)
+    }
+
+    /// Writes a report of the duplicated crates, with a hint on how to unify each of them and the
+    /// parents, their requirements and the paths of each version.
+    pub fn write_duplicates<W: Write>(&self, duplicates: &[Duplicate], w: &mut W) -> Result<()> {
+        match duplicates.len() {
+            0 => return Ok(writeln!(w, "No crates with several versions")?),
+            1 => writeln!(w, "1 crate with several versions:")?,
+            len => writeln!(w, "{} crates with several versions:", len)?,
+        }
+
+        for duplicate in duplicates {
+            writeln!(w)?;
+            writeln!(
+                w,
+                "{} ({} versions, {} extra {})",
+                duplicate.name,
+                duplicate.versions.len(),
+                duplicate.extra_crates,
+                if duplicate.extra_crates == 1 {
+                    "crate"
+                } else {
+                    "crates"
+                }
+            )?;
+            writeln!(w, "  hint: {}", self.hint(duplicate))?;
+
+            for version in duplicate.versions.iter() {
+                writeln!(w, "  {}", self.describe(version.node))?;
+                let parents = version
+                    .parents
+                    .iter()
+                    .zip(version.requirements.iter())
+                    .map(|(&parent, req)| match req {
+                        Some(req) => format!("{} (\"{}\")", self.describe(parent), req),
+                        None => self.describe(parent),
+                    })
+                    .collect::<Vec<_>>();
+                writeln!(w, "    required by: {}", parents.join(", "))?;
+
+                for path in version.paths.paths.iter() {
+                    let path = path
+                        .nodes
+                        .iter()
+                        .map(|&n| self.describe(n))
+                        .collect::<Vec<_>>();
+                    writeln!(w, "    {}", path.join(" -> "))?;
+                }
+                let hidden = version.paths.count - version.paths.paths.len() as u128;
+                if hidden > 0 {
+                    writeln!(w, "    ... and {} more paths", hidden)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Explains how the versions of a duplicated crate could be unified.
+    fn hint(&self, duplicate: &Duplicate) -> String {
+        let describe_all = |nodes: &[Node]| {
+            nodes
+                .iter()
+                .map(|&n| self.describe(n))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let newest = duplicate.versions[duplicate.versions.len() - 1].node;
+
+        match duplicate.unification {
+            Unification::CargoUpdate(n) => format!(
+                "every requirement allows v{}, so `cargo update` would unify on it",
+                self.nodes[n].ver
+            ),
+            Unification::ParentUpgrade(ref parents) => format!(
+                "semver compatible, but needs an upgrade of {}, which {} not allow v{}",
+                describe_all(parents),
+                if parents.len() == 1 { "does" } else { "do" },
+                self.nodes[newest].ver
+            ),
+            Unification::IncompatibleMajors => {
+                "incompatible major versions, the parents of the older versions need upgrading"
+                    .into()
+            }
+            Unification::SeveralSources => {
+                "comes from several sources, which only changing the parents or a [patch] would \
+                 unify"
+                    .into()
+            }
+            Unification::Unknown(ref parents) if parents.is_empty() => "unknown".into(),
+            Unification::Unknown(ref parents) => format!(
+                "unknown, the requirements of {} were not found locally",
+                describe_all(parents)
+            ),
+        }
+    }
+
+    // Describes a node as `name vVERSION`, with its source if another node has the same version.
+    fn describe(&self, n: Node) -> String {
+        let dep = &self.nodes[n];
+        match dep.source {
+            Some(ref source) if dep.force_write_source => {
+                format!("{} v{} ({})", dep.name, dep.ver, source)
+            }
+            _ => format!("{} v{}", dep.name, dep.ver),
+        }
+    }
+
+    // Counts the crates that would no longer be reachable from the root crates if only one of the
+    // given nodes was kept, keeping the one that leaves the fewest.
+    fn extra_crates(&self, nodes: &[Node]) -> usize {
+        let children = self.adjacency(false);
+        let roots = (0..self.nodes.len())
+            .map(|n| self.is_root(n) && !self.nodes[n].hidden)
+            .collect::<Vec<_>>();
+        let reached = ops::reachable(&children, &roots, &[])
+            .into_iter()
+            .filter(|&reached| reached)
+            .count();
+
+        nodes
+            .iter()
+            .map(|&kept| {
+                let mut blocked = vec![false; self.nodes.len()];
+                for &n in nodes.iter().filter(|&&n| n != kept) {
+                    blocked[n] = true;
+                }
+                let still_reached = ops::reachable(&children, &roots, &blocked)
+                    .into_iter()
+                    .filter(|&reached| reached)
+                    .count();
+                reached - still_reached
+            })
+            .min()
+            .unwrap_or(0)
+    }
+}
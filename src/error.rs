@@ -1,16 +1,78 @@
 use std::{
+    error,
     fmt::{self, Display, Formatter},
     io,
+    path::{Path, PathBuf},
 };
 
 /// Result type for the crate.
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Error type for the crate.
+///
+/// `Error::exit` exits with a distinct code for each category of error:
+///
+/// | Code | Errors |
+/// |------|--------|
+/// | 1    | `Io`, `Generic` |
+/// | 2    | `InvalidConfig` |
+/// | 3    | `ManifestNotFound`, `LockfileNotFound` |
+/// | 4    | `Toml`, `TomlParse`, `InvalidFile` |
+/// | 5    | `VersionMismatch` |
+/// | 6    | `CycleDetected` |
+/// | 7    | `DuplicatesFound` |
 #[derive(Debug)]
 pub enum Error {
-    /// Errors originating from the toml crate.
-    Toml(String),
+    /// No Cargo.toml was found at the given path or in any parent directory.
+    ManifestNotFound(PathBuf),
+    /// No Cargo.lock was found next to the manifest or in any parent directory.
+    LockfileNotFound(PathBuf),
+    /// Errors originating from the toml crate, for input that was not read from a file.
+    Toml(toml::de::Error),
+    /// A manifest or lock file that is not valid TOML.
+    TomlParse {
+        /// The file that could not be parsed.
+        path: PathBuf,
+        /// Line and column of the error, starting at 1, if known.
+        line_col: Option<(usize, usize)>,
+        /// The error reported by the TOML parser.
+        source: toml::de::Error,
+    },
+    /// A manifest or lock file with missing or invalid fields.
+    InvalidFile {
+        /// The file with the invalid content.
+        path: PathBuf,
+        /// Index and, if known, name of the offending `[[package]]` entry of a lock file.
+        package: Option<(usize, Option<String>)>,
+        /// The offending key, e.g. `version` or `workspace.members`.
+        key: Option<String>,
+        /// What is wrong with the content.
+        message: String,
+    },
+    /// The lock file does not match the version of a root crate in its manifest, which usually
+    /// means that the lock file is out of date.
+    VersionMismatch {
+        /// The name of the root crate.
+        name: String,
+        /// The version in the manifest.
+        manifest_ver: String,
+        /// The version in the lock file, or `None` if the crate is missing from it.
+        lockfile_ver: Option<String>,
+    },
+    /// The dependency graph contains cycles.
+    CycleDetected {
+        /// The crates in each cycle, starting and ending with the same crate. Every edge that is on
+        /// a cycle is on one of these.
+        cycles: Vec<Vec<String>>,
+    },
+    /// The dependency graph contains crates in several versions, and `--fail-on-duplicates` was
+    /// given.
+    DuplicatesFound {
+        /// The names of the duplicated crates.
+        crates: Vec<String>,
+    },
+    /// Invalid options, such as an unknown target or feature.
+    InvalidConfig(String),
     /// IO errors.
     Io(io::Error),
     /// All other errors.
@@ -20,18 +82,130 @@ pub enum Error {
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match *self {
+            Self::ManifestNotFound(ref path) => write!(
+                f,
+                "Could not find {:?} or a Cargo.toml in any parent directory",
+                path
+            ),
+            Self::LockfileNotFound(ref path) => write!(
+                f,
+                "Could not find {:?} or a Cargo.lock in any parent directory",
+                path
+            ),
+            Self::Toml(_) => write!(f, "Could not parse input as TOML"),
+            Self::TomlParse {
+                ref path, line_col, ..
+            } => {
+                write!(f, "Could not parse {}", path.display())?;
+                if let Some((line, col)) = line_col {
+                    write!(f, ":{}:{}", line, col)?;
+                }
+                Ok(())
+            }
+            Self::InvalidFile {
+                ref path,
+                ref package,
+                ref key,
+                ref message,
+            } => {
+                write!(f, "Invalid {}", path.display())?;
+                if let Some((index, ref name)) = *package {
+                    write!(f, ", package[{}]", index)?;
+                    if let Some(name) = name {
+                        write!(f, " ({})", name)?;
+                    }
+                }
+                if let Some(key) = key {
+                    write!(f, ", key '{}'", key)?;
+                }
+                write!(f, ": {}", message)
+            }
+            Self::VersionMismatch {
+                ref name,
+                ref manifest_ver,
+                lockfile_ver: Some(ref lockfile_ver),
+            } => write!(
+                f,
+                "Version {} of root crate '{}' in Cargo.lock does not match version {} in \
+                 Cargo.toml",
+                lockfile_ver, name, manifest_ver
+            ),
+            Self::VersionMismatch {
+                ref name,
+                ref manifest_ver,
+                lockfile_ver: None,
+            } => write!(
+                f,
+                "Root crate '{}' {} was not found in Cargo.lock",
+                name, manifest_ver
+            ),
+            Self::CycleDetected { ref cycles } => {
+                write!(f, "Cycles detected in dependency graph:")?;
+                for cycle in cycles {
+                    write!(f, "\n    {}", cycle.join(" -> "))?;
+                }
+                write!(f, "\nUse --allow-cycles to render the graph anyway")
+            }
+            Self::DuplicatesFound { ref crates } => write!(
+                f,
+                "Found {} {} with several versions: {}",
+                crates.len(),
+                if crates.len() == 1 { "crate" } else { "crates" },
+                crates.join(", ")
+            ),
+            Self::InvalidConfig(ref e) => write!(f, "{}", e),
+            Self::Io(_) => write!(f, "I/O error"),
             Self::Generic(ref e) => write!(f, "{}", e),
-            Self::Toml(ref e) => write!(f, "Could not parse toml file: {}", e),
-            Self::Io(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Toml(ref e) | Self::TomlParse { source: ref e, .. } => Some(e),
+            Self::Io(ref e) => Some(e),
+            _ => None,
         }
     }
 }
 
 impl Error {
-    /// Print this error and immediately exit the program.
+    // Builds an `InvalidFile` error for a key of a manifest or lock file.
+    pub(crate) fn invalid_key<P: AsRef<Path>>(path: P, key: &str, message: &str) -> Self {
+        Self::InvalidFile {
+            path: path.as_ref().to_path_buf(),
+            package: None,
+            key: Some(key.to_string()),
+            message: message.to_string(),
+        }
+    }
+
+    /// The code that `exit` exits with for this error.
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Self::Io(_) | Self::Generic(_) => 1,
+            Self::InvalidConfig(_) => 2,
+            Self::ManifestNotFound(_) | Self::LockfileNotFound(_) => 3,
+            Self::Toml(_) | Self::TomlParse { .. } | Self::InvalidFile { .. } => 4,
+            Self::VersionMismatch { .. } => 5,
+            Self::CycleDetected { .. } => 6,
+            Self::DuplicatesFound { .. } => 7,
+        }
+    }
+
+    /// Print this error and its sources and immediately exit the program with the code given by
+    /// `exit_code`.
     pub fn exit(&self) -> ! {
-        eprintln!("error: {}", self);
-        ::std::process::exit(1)
+        eprint!("error: {}", self);
+        let mut source = error::Error::source(self);
+        while let Some(err) = source {
+            eprint!(": {}", err);
+            source = err.source();
+        }
+        eprintln!();
+
+        ::std::process::exit(self.exit_code())
     }
 }
 
@@ -43,6 +217,6 @@ impl From<io::Error> for Error {
 
 impl From<toml::de::Error> for Error {
     fn from(err: toml::de::Error) -> Self {
-        Self::Toml(format!("Could not parse input as TOML: {}", err))
+        Self::Toml(err)
     }
 }
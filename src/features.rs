@@ -0,0 +1,163 @@
+use crate::error::{Error, Result};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+use toml::Value;
+
+/// Map of optional dep names, as written in the manifest, to the features that enable them.
+pub type EnabledDepsMap = HashMap<String, Vec<String>>;
+
+/// Evaluates the [features] table of a manifest and returns the optional deps that are enabled by
+/// the selected features. Also returns the selected features that the manifest does not define.
+///
+/// Supports `dep:name` entries, implicit features for optional deps, and `name/feature` entries,
+/// which enable the optional dep `name`. Weak `name?/feature` entries never enable a dep. Selected
+/// features can also be given as `name/feature` or `name?/feature`, like with `cargo build`, where
+/// `name` is any dependency of the manifest.
+pub fn enabled_optional_deps<'a>(
+    manifest_path: &Path,
+    manifest_toml: &Value,
+    selected: &[&'a str],
+    all_features: bool,
+    no_default_features: bool,
+) -> Result<(EnabledDepsMap, Vec<&'a str>)> {
+    let features = parse_features_table(manifest_path, manifest_toml)?;
+    let (dep_names, optional_deps) = dep_names(manifest_toml);
+
+    // Optional deps have an implicit feature of the same name, unless a `dep:` entry refers to
+    // them anywhere in the [features] table.
+    let has_dep_entry = features
+        .values()
+        .flatten()
+        .filter_map(|entry| entry.strip_prefix("dep:"))
+        .collect::<HashSet<_>>();
+    let implicit_features = optional_deps
+        .iter()
+        .filter(|dep| !has_dep_entry.contains(dep.as_str()) && !features.contains_key(*dep))
+        .collect::<HashSet<_>>();
+
+    let mut queue = vec![];
+    let mut unknown = vec![];
+    if all_features {
+        queue.extend(features.keys().cloned());
+        queue.extend(implicit_features.iter().map(|dep| dep.to_string()));
+    } else if !no_default_features && features.contains_key("default") {
+        queue.push("default".to_string());
+    }
+    let mut enabled_deps = EnabledDepsMap::new();
+    for &feature in selected.iter() {
+        if features.contains_key(feature) || implicit_features.contains(&feature.to_string()) {
+            queue.push(feature.to_string());
+        } else if let Some(i) = feature.find('/') {
+            // Selecting a feature of a dependency enables the dependency too, unless it is weak.
+            let dep = &feature[..i];
+            if optional_deps.contains(dep) {
+                enable_dep(&mut enabled_deps, dep, feature);
+            } else if !dep_names.contains(dep.trim_end_matches('?')) {
+                unknown.push(feature);
+            }
+        } else {
+            unknown.push(feature);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    while let Some(feature) = queue.pop() {
+        if !seen.insert(feature.clone()) {
+            continue;
+        }
+
+        if implicit_features.contains(&feature) {
+            enable_dep(&mut enabled_deps, &feature, &feature);
+        }
+
+        for entry in features.get(&feature).into_iter().flatten() {
+            if let Some(dep) = entry.strip_prefix("dep:") {
+                enable_dep(&mut enabled_deps, dep, &feature);
+            } else if let Some(i) = entry.find('/') {
+                let dep = &entry[..i];
+                // `dep?/feature` only enables the feature if the dep is already enabled.
+                if !dep.ends_with('?') && optional_deps.contains(dep) {
+                    enable_dep(&mut enabled_deps, dep, &feature);
+                }
+            } else if implicit_features.contains(entry) {
+                enable_dep(&mut enabled_deps, entry, &feature);
+            } else {
+                queue.push(entry.clone());
+            }
+        }
+    }
+
+    for features in enabled_deps.values_mut() {
+        features.sort();
+    }
+
+    Ok((enabled_deps, unknown))
+}
+
+fn enable_dep(enabled_deps: &mut EnabledDepsMap, dep: &str, feature: &str) {
+    let features = enabled_deps.entry(dep.to_string()).or_default();
+    if !features.iter().any(|f| f == feature) {
+        features.push(feature.to_string());
+    }
+}
+
+fn parse_features_table(
+    manifest_path: &Path,
+    manifest_toml: &Value,
+) -> Result<HashMap<String, Vec<String>>> {
+    let mut features = HashMap::new();
+
+    if let Some(table) = manifest_toml.get("features") {
+        let table = table
+            .as_table()
+            .ok_or_else(|| Error::invalid_key(manifest_path, "features", "expected a table"))?;
+
+        for (feature, entries) in table.iter() {
+            let entries = entries
+                .as_array()
+                .and_then(|entries| {
+                    entries
+                        .iter()
+                        .map(|entry| entry.as_str().map(String::from))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| {
+                    Error::invalid_key(
+                        manifest_path,
+                        &format!("features.{}", feature),
+                        "expected an array of strings",
+                    )
+                })?;
+            features.insert(feature.clone(), entries);
+        }
+    }
+
+    Ok(features)
+}
+
+// Gets the names of all deps and of the optional ones, including platform-specific deps.
+fn dep_names(manifest_toml: &Value) -> (HashSet<String>, HashSet<String>) {
+    let mut deps_tomls = vec![manifest_toml];
+    if let Some(Value::Table(targets)) = manifest_toml.get("target") {
+        deps_tomls.extend(targets.iter().map(|(_, target_toml)| target_toml));
+    }
+
+    let mut names = HashSet::new();
+    let mut optional_names = HashSet::new();
+    for deps_toml in deps_tomls {
+        for table_name in &["dependencies", "build-dependencies", "dev-dependencies"] {
+            if let Some(Value::Table(table)) = deps_toml.get(table_name) {
+                for (dep_name, dep_table) in table.iter() {
+                    names.insert(dep_name.clone());
+                    if let Some(Value::Boolean(true)) = dep_table.get("optional") {
+                        optional_names.insert(dep_name.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    (names, optional_names)
+}
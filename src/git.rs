@@ -0,0 +1,144 @@
+use crate::{
+    error::{Error, Result},
+    util,
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The files of the local git repository as they were at a revision, e.g. `HEAD~1` or `main`. They
+/// are read by running the `git` binary, without touching the working tree.
+#[derive(Clone, Debug)]
+pub struct Revision {
+    rev: String,
+    /// The top-level directory of the repository, which the paths in git are relative to.
+    top_level: PathBuf,
+    /// The files at the revision, relative to the top-level directory.
+    files: HashSet<PathBuf>,
+}
+
+impl Revision {
+    /// Lists the files at `rev` in the repository that contains `dir`. Fails with
+    /// `Error::InvalidConfig` if `dir` is not in a git repository or the revision does not exist.
+    pub fn open(rev: &str, dir: &Path) -> Result<Self> {
+        let open_error = |message: &str| {
+            Error::InvalidConfig(format!(
+                "Could not read git revision '{}': {}",
+                rev, message
+            ))
+        };
+        // Revisions are passed as arguments, so they must not look like options.
+        if rev.is_empty() || rev.starts_with('-') {
+            return Err(open_error("not a valid revision"));
+        }
+
+        let top_level = git(dir, &["rev-parse", "--show-toplevel"]).map_err(|e| open_error(&e))?;
+        let top_level = PathBuf::from(String::from_utf8_lossy(&top_level).trim_end());
+        let files = git(&top_level, &["ls-tree", "-r", "-z", "--name-only", rev])
+            .map_err(|e| open_error(&e))?;
+        let files = files
+            .split(|&b| b == 0)
+            .filter(|file| !file.is_empty())
+            .map(|file| PathBuf::from(String::from_utf8_lossy(file).into_owned()))
+            .collect();
+
+        Ok(Self {
+            rev: rev.into(),
+            top_level,
+            files,
+        })
+    }
+
+    /// Names a file at the revision as `REV:PATH`, like git does, for use in errors.
+    pub fn name(&self, path: &Path) -> PathBuf {
+        PathBuf::from(format!("{}:{}", self.rev, path.display()))
+    }
+
+    /// Checks whether the file exists at the revision.
+    pub fn is_file(&self, path: &Path) -> bool {
+        self.relative(path)
+            .is_some_and(|path| self.files.contains(&path))
+    }
+
+    /// Checks whether the directory contains any files at the revision. Git does not track empty
+    /// directories.
+    pub fn is_dir(&self, path: &Path) -> bool {
+        self.relative(path).is_some_and(|path| {
+            self.files
+                .iter()
+                .any(|file| file.starts_with(&path) && *file != path)
+        })
+    }
+
+    /// Lists the files and directories directly in a directory at the revision, in sorted order.
+    pub fn read_dir(&self, path: &Path) -> Vec<PathBuf> {
+        let dir = match self.relative(path) {
+            Some(dir) => dir,
+            None => return vec![],
+        };
+        let mut entries = self
+            .files
+            .iter()
+            .filter_map(|file| file.strip_prefix(&dir).ok())
+            .filter_map(|rest| rest.components().next())
+            .map(|name| path.join(name))
+            .collect::<Vec<_>>();
+        entries.sort();
+        entries.dedup();
+        entries
+    }
+
+    /// Reads a file as it was at the revision, by running `git show`. Fails with
+    /// `Error::InvalidConfig` if the file does not exist at the revision.
+    pub fn read_to_string(&self, path: &Path) -> Result<String> {
+        let read_error = |message: &str| {
+            Error::InvalidConfig(format!(
+                "Could not read '{}' at git revision '{}': {}",
+                path.display(),
+                self.rev,
+                message
+            ))
+        };
+        let relative = self
+            .relative(path)
+            .ok_or_else(|| read_error("the file is outside of the repository"))?;
+
+        // Git always separates the components of its paths with `/`.
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let bytes = git(
+            &self.top_level,
+            &["show", &format!("{}:{}", self.rev, relative)],
+        )
+        .map_err(|e| read_error(&e))?;
+        String::from_utf8(bytes).map_err(|e| read_error(&e.to_string()))
+    }
+
+    // Makes a path relative to the top-level directory, resolving `.` and `..` components. Returns
+    // `None` if the path is outside of the repository.
+    fn relative(&self, path: &Path) -> Option<PathBuf> {
+        util::normalize_path(path)
+            .strip_prefix(&self.top_level)
+            .ok()
+            .map(Path::to_path_buf)
+    }
+}
+
+// Runs git in a directory and returns its output, or the error that it printed.
+fn git(dir: &Path, args: &[&str]) -> std::result::Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("could not run git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+    }
+    Ok(output.stdout)
+}
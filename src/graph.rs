@@ -1,56 +1,104 @@
 use crate::{
+    closure::TransitiveClosure,
+    cluster::{self, Cluster},
     config::Config,
-    dep::{DepKind, ResolvedDep},
+    cycles,
+    dep::{DepKind, ResolvedDep, RootCrate},
     error::{Error, Result},
-    project::RootDepsMap,
+    project::{RootDepsMap, RootFeaturesMap, RootPlatformsMap, RootReqsMap},
+    selector::CrateSelector,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::Write,
+    path::PathBuf,
 };
-use std::{collections::HashMap, fmt, io::Write};
 
 pub type Node = usize;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
 pub struct Edge(pub Node, pub Node);
 
 impl Edge {
     pub fn label<W: Write>(&self, w: &mut W, dg: &DepGraph) -> Result<()> {
-        use crate::dep::DepKind::{Build, Dev, Optional, Regular, Unknown};
-
         let parent = dg.get(self.0).unwrap();
         let child = dg.get(self.1).unwrap();
+        let is_root = dg.is_root(self.0);
 
-        // Special case: always color edge from root to root dep by its actual root dependency kind.
-        // Otherwise, the root dep could also be a dep of a regular dep which will cause the root ->
-        // root dep edge to appear regular, which is misleading as it is not regular in Cargo.toml.
-        let child_kind = if let Some(dep_kinds_map) = &dg.root_deps_map.get(&parent.name) {
-            if let Some(kinds) = dep_kinds_map.get(&child.name) {
-                if kinds.contains(&Regular) {
-                    Regular
-                } else if kinds.contains(&Build) {
-                    Build
-                } else if kinds.contains(&Dev) {
-                    Dev
-                } else if kinds.contains(&Optional) {
-                    Optional
-                } else {
-                    Unknown
-                }
-            } else {
-                return Err(Error::Generic(format!(
-                    "Crate '{}' is not a dependency of a root crate. This is probably a logic \
-                     error.",
-                    child.name
-                )));
-            }
-        } else {
+        // Contracted edges and dominator tree edges between crates that do not depend on each other
+        // directly stand for paths, which have the kind of the crate they lead to.
+        let contracted = dg.contracted_edges.get(self);
+        let indirect = contracted.is_some() || dg.dominator_edges.contains(self);
+        let kind = if indirect {
             child.kind()
+        } else {
+            dg.edge_kind(self.0, self.1)?
+        };
+        let mut attrs = match kind {
+            DepKind::Regular => vec![],
+            DepKind::Build => vec!["color=purple".into(), "style=dashed".into()],
+            DepKind::Dev => vec!["color=blue".into(), "style=dashed".into()],
+            DepKind::Optional => vec!["color=red".into(), "style=dashed".into()],
+            DepKind::Unknown => vec!["color=orange".into(), "style=dashed".into()],
         };
 
-        match (parent.kind(), child_kind) {
-            (Regular, Regular) => writeln!(w, ";")?,
-            (Build, _) | (Regular, Build) => writeln!(w, " [color=purple, style=dashed];")?,
-            (Dev, _) | (Regular, Dev) => writeln!(w, " [color=blue, style=dashed];")?,
-            (Optional, _) | (Regular, Optional) => writeln!(w, " [color=red, style=dashed];")?,
-            _ => writeln!(w, " [color=orange, style=dashed];")?,
+        // Dotted edges either stand for paths, where contracted edges are labelled with the number
+        // of crates they go through, or are from a root crate and only compiled on some platforms.
+        // Edges to optional deps are labelled with the features that enable them.
+        let mut label = vec![];
+        if indirect {
+            attrs.retain(|attr: &String| !attr.starts_with("style="));
+            attrs.push("style=dotted".into());
+        }
+        if let Some(&via) = contracted {
+            label.push(match via {
+                1 => "via 1 crate".into(),
+                via => format!("via {} crates", via),
+            });
+        } else if let Some(platforms) = dg
+            .root_platforms_map
+            .get(&parent.name)
+            .filter(|_| is_root)
+            .and_then(|dep_platforms_map| dep_platforms_map.get(&child.name))
+        {
+            attrs.retain(|attr: &String| !attr.starts_with("style="));
+            attrs.push("style=dotted".into());
+            label.extend(platforms.iter().cloned());
+        }
+        if let Some(features) = dg
+            .root_features_map
+            .get(&parent.name)
+            .filter(|_| is_root)
+            .and_then(|dep_features_map| dep_features_map.get(&child.name))
+        {
+            label.extend(
+                features
+                    .iter()
+                    .map(|feature| format!("feature = \"{}\"", feature)),
+            );
+        }
+        // Edges to dev-only deps may point back up the graph, so they do not affect the layout.
+        if dg.dev_only_edges.contains(self) {
+            attrs.push("constraint=false".into());
+        }
+        // Edges on a cycle are drawn in bold red.
+        if dg.cycle_edges.contains(self) {
+            attrs.retain(|attr: &String| !attr.starts_with("color="));
+            attrs.push("color=red".into());
+            attrs.push("penwidth=2".into());
+        }
+        if !label.is_empty() {
+            attrs.push(format!(
+                "label=\"{}\"",
+                label.join("\\n").replace('"', "\\\"")
+            ));
+        }
+
+        if attrs.is_empty() {
+            writeln!(w, ";")?;
+        } else {
+            writeln!(w, " [{}];", attrs.join(", "))?;
         }
 
         Ok(())
@@ -64,13 +112,52 @@ impl fmt::Display for Edge {
     }
 }
 
+// How a cluster is rendered.
+struct ClusterStyle {
+    id: String,
+    label: Option<String>,
+    color: String,
+}
+
 #[derive(Debug)]
 pub struct DepGraph {
     /// Vector of nodes containing resolved dependency information as well as the indices of parent
     /// and children nodes.
     pub nodes: Vec<ResolvedDep>,
     pub edges: Vec<Edge>,
+    /// The root crates, which are local packages without a source. The root maps are keyed by
+    /// their names, which are unique within a workspace, so look nodes up there only after
+    /// checking `is_root`.
+    pub root_crates: Vec<RootCrate>,
     pub root_deps_map: RootDepsMap,
+    pub root_platforms_map: RootPlatformsMap,
+    pub root_features_map: RootFeaturesMap,
+    /// The version requirements with which each root crate declares its dependencies.
+    pub root_reqs_map: RootReqsMap,
+    /// The directory made by `cargo vendor` next to the workspace root, if there is one.
+    pub vendor_dir: Option<PathBuf>,
+    /// The format version of the lock file the graph was built from.
+    pub lockfile_version: Option<u32>,
+    /// The cycles that were allowed with `allow_cycles`, each starting and ending with the same
+    /// node. Every edge that is on a cycle is on one of these.
+    pub cycles: Vec<Vec<Node>>,
+    /// The edges on the allowed cycles.
+    pub(crate) cycle_edges: HashSet<Edge>,
+    /// Map of crate names to their nodes, for fast lookup.
+    nodes_by_name: HashMap<String, Vec<Node>>,
+    /// Edges from a root crate to a crate that it only dev-depends on. Cargo allows cycles through
+    /// such edges, so they are ignored when ordering the graph.
+    pub dev_only_edges: HashSet<Edge>,
+    /// Edges that stand for paths through crates that were filtered out, with the number of those
+    /// crates.
+    pub contracted_edges: HashMap<Edge, usize>,
+    /// Edges of the dominator tree, as set by `retain_dominator_tree`, between crates that do not
+    /// depend on each other directly.
+    pub dominator_edges: HashSet<Edge>,
+    /// Named groups of crates, each rendered as its own cluster.
+    pub clusters: Vec<Cluster>,
+    /// Whether edges are rendered from each crate to its dependents, as set by `invert`.
+    pub inverted: bool,
     pub cfg: Config,
 }
 
@@ -79,86 +166,224 @@ impl DepGraph {
         Self {
             nodes: vec![],
             edges: vec![],
+            root_crates: vec![],
             root_deps_map: HashMap::new(),
+            root_platforms_map: HashMap::new(),
+            root_features_map: HashMap::new(),
+            root_reqs_map: HashMap::new(),
+            vendor_dir: None,
+            lockfile_version: None,
+            cycles: vec![],
+            cycle_edges: HashSet::new(),
+            nodes_by_name: HashMap::new(),
+            dev_only_edges: HashSet::new(),
+            contracted_edges: HashMap::new(),
+            dominator_edges: HashSet::new(),
+            clusters: vec![],
+            inverted: false,
             cfg,
         }
     }
 
-    /// Performs a topological sort on the edges.
+    /// Performs a topological sort on the edges, ignoring dev-only edges. Fails with the list of
+    /// cycles if the graph has any, unless cycles are allowed, in which case the nodes of each cycle
+    /// are ordered after the nodes leading to it and the cycles are kept in `cycles`.
     pub fn topological_sort(&mut self) -> Result<()> {
-        // Create a clone of the nodes list so we can remove parents and children without affecting
-        // the original list. We work on the original list of edges, clearing it as we go, because
-        // we construct a new one at the end, in topological order.
-        let mut graph_nodes = self.nodes.clone();
+        // Adjacency lists without the dev-only edges.
+        let children = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(n, node)| {
+                node.children
+                    .iter()
+                    .copied()
+                    .filter(|&child| !self.dev_only_edges.contains(&Edge(n, child)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // Kahn's algorithm: count the incoming edges of each node, and remove the edges of each node
+        // once it is sorted.
+        let mut in_degree = vec![0; self.nodes.len()];
+        for &child in children.iter().flatten() {
+            in_degree[child] += 1;
+        }
         // Will contain indices of the nodes in sorted order.
-        let mut l: Vec<Node> = vec![];
+        let mut l: Vec<Node> = Vec::with_capacity(self.nodes.len());
         // Set of nodes with no incoming edges.
-        let mut s: Vec<Node> = vec![];
-
-        // Populate initial list of start nodes which have no incoming edges.
-        for (i, node) in self.nodes.iter().enumerate() {
-            if node.parents.is_empty() {
-                s.push(i);
-            }
-        }
+        let mut s: Vec<Node> = (0..self.nodes.len())
+            .filter(|&n| in_degree[n] == 0)
+            .collect();
 
         while let Some(n) = s.pop() {
             l.push(n);
 
-            while let Some(child) = graph_nodes[n].children.pop() {
-                assert_ne!(n, child);
-
-                // Remove the edge from n -> child.
-                let edge_index = self
-                    .edges
-                    .iter()
-                    .position(|Edge(a, b)| a == &n && b == &child)
-                    .unwrap();
-                self.edges.remove(edge_index);
-                let node_index = graph_nodes[child]
-                    .parents
-                    .iter()
-                    .position(|node| *node == n)
-                    .unwrap();
-                graph_nodes[child].parents.remove(node_index);
+            for &child in children[n].iter().rev() {
+                in_degree[child] -= 1;
 
                 // If child has no other parents, it is in the next topological level.
-                if graph_nodes[child].parents.is_empty() {
+                if in_degree[child] == 0 {
                     s.push(child);
                 }
             }
         }
 
-        if self.edges.is_empty() {
-            // Add back the edges, this time in topological order.
-            for n in l.iter() {
-                'child_loop: for child in self.nodes[*n].children.iter() {
-                    // Push an edge for each child, unless filtering of transitive deps is enabled,
-                    // in which case skip to the next child if a transitive dependency exists to the
-                    // child through one of the other children nodes.
-                    if !self.cfg.transitive_deps {
-                        for c in self.nodes[*n].children.iter().filter(|c| *c != child) {
-                            if self.transitive_dep(*c, *child) {
-                                continue 'child_loop;
-                            }
-                        }
+        if l.len() < self.nodes.len() {
+            // The remaining nodes are on or below a cycle. Order them by their strongly connected
+            // components, which are in topological order themselves.
+            let remaining = (0..self.nodes.len())
+                .filter(|&n| in_degree[n] > 0)
+                .collect::<Vec<_>>();
+            let components = cycles::strongly_connected_components(&children, &remaining);
+
+            let mut cycles = vec![];
+            for component in components.iter().filter(|component| component.len() > 1) {
+                cycles.extend(cycles::covering_cycles(&children, component));
+            }
+            for cycle in cycles.iter_mut() {
+                // Start each cycle at its first crate by name, for stable output.
+                cycle.pop();
+                let first = (0..cycle.len())
+                    .min_by_key(|&i| &self.nodes[cycle[i]].name)
+                    .unwrap();
+                cycle.rotate_left(first);
+                cycle.push(cycle[0]);
+            }
+            cycles.sort_by_key(|cycle| self.cycle_names(cycle));
+
+            if !self.cfg.allow_cycles {
+                return Err(Error::CycleDetected {
+                    cycles: cycles.iter().map(|cycle| self.cycle_names(cycle)).collect(),
+                });
+            }
+            l.extend(components.into_iter().flatten());
+            self.cycle_edges = cycles
+                .iter()
+                .flat_map(|cycle| cycle.windows(2).map(|pair| Edge(pair[0], pair[1])))
+                .collect();
+            self.cycles = cycles;
+        }
+
+        // Filtering of transitive deps needs to know which nodes are reachable from each node.
+        let closure = if self.cfg.transitive_deps {
+            None
+        } else {
+            let all_nodes = (0..self.nodes.len()).collect::<Vec<_>>();
+            let components = cycles::strongly_connected_components(&children, &all_nodes);
+            Some(TransitiveClosure::new(&children, &components))
+        };
+
+        // Add back the edges, this time in topological order.
+        self.edges.clear();
+        for &n in l.iter() {
+            for &child in self.nodes[n].children.iter() {
+                let edge = Edge(n, child);
+
+                // Push an edge for each child, unless filtering of transitive deps is enabled,
+                // in which case skip to the next child if a transitive dependency exists to the
+                // child through one of the other children nodes. Edges on a cycle are always kept.
+                // Paths through dev-only edges do not count.
+                if let Some(ref closure) = closure {
+                    if !self.cycle_edges.contains(&edge)
+                        && children[n]
+                            .iter()
+                            .any(|&c| c != child && closure.reaches(c, child))
+                    {
+                        continue;
                     }
-                    self.edges.push(Edge(*n, *child));
                 }
+                self.edges.push(edge);
             }
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether a node is one of the root crates, which are local packages without a source.
+    /// Other crates can have the same name as a root crate, e.g. an older version of it from a
+    /// registry, so the version is compared as well.
+    pub fn is_root(&self, node: Node) -> bool {
+        let dep = &self.nodes[node];
+        dep.source.is_none()
+            && self
+                .root_crates
+                .iter()
+                .any(|root| root.name == dep.name && root.ver == dep.ver)
+    }
+
+    /// Gets the kind of dependency that an edge stands for.
+    pub fn edge_kind(&self, parent: Node, child: Node) -> Result<DepKind> {
+        use crate::dep::DepKind::{Build, Dev, Optional, Regular, Unknown};
 
-            Ok(())
+        let is_root = self.is_root(parent);
+        let parent = &self.nodes[parent];
+        let child = &self.nodes[child];
+
+        // Special case: always color edge from root to root dep by its actual root dependency kind.
+        // Otherwise, the root dep could also be a dep of a regular dep which will cause the root ->
+        // root dep edge to appear regular, which is misleading as it is not regular in Cargo.toml.
+        let root_deps = self.root_deps_map.get(&parent.name).filter(|_| is_root);
+        let child_kind = if let Some(dep_kinds_map) = root_deps {
+            if let Some(kinds) = dep_kinds_map.get(&child.name) {
+                if kinds.contains(&Regular) {
+                    Regular
+                } else if kinds.contains(&Build) {
+                    Build
+                } else if kinds.contains(&Dev) {
+                    Dev
+                } else if kinds.contains(&Optional) {
+                    Optional
+                } else {
+                    Unknown
+                }
+            } else {
+                return Err(Error::Generic(format!(
+                    "Crate '{}' is not a dependency of a root crate. This is probably a logic \
+                     error.",
+                    child.name
+                )));
+            }
         } else {
-            Err(Error::Generic("Cycle detected in dependency graph".into()))
-        }
+            child.kind()
+        };
+
+        Ok(match (parent.kind(), child_kind) {
+            (Regular, Regular) => Regular,
+            (Build, _) | (Regular, Build) => Build,
+            (Dev, _) | (Regular, Dev) => Dev,
+            (Optional, _) | (Regular, Optional) => Optional,
+            _ => Unknown,
+        })
+    }
+
+    /// Describes each of the allowed cycles by the names of its crates, e.g. `a -> b -> a`.
+    pub fn describe_cycles(&self) -> Vec<String> {
+        self.cycles
+            .iter()
+            .map(|cycle| self.cycle_names(cycle).join(" -> "))
+            .collect()
+    }
+
+    // Gets the crate names of the nodes in a cycle.
+    fn cycle_names(&self, cycle: &[Node]) -> Vec<String> {
+        cycle
+            .iter()
+            .map(|&node| self.nodes[node].name.clone())
+            .collect()
     }
 
     /// Sets the kind of each dependency based on how the dependencies are declared in the manifest.
     pub fn set_resolved_kind(&mut self) -> Result<()> {
-        // Set regular kind for all root nodes.
-        for node in self.nodes.iter_mut() {
-            if self.root_deps_map.contains_key(&node.name) {
+        // Set regular kind and depth 0 for all root nodes, including the workspace members that
+        // other members depend on.
+        let roots = (0..self.nodes.len())
+            .map(|n| self.is_root(n))
+            .collect::<Vec<_>>();
+        for (node, &is_root) in self.nodes.iter_mut().zip(roots.iter()) {
+            if is_root {
                 node.is_regular = true;
+                node.depth = Some(0);
             }
         }
 
@@ -189,14 +414,15 @@ impl DepGraph {
                     parent.is_optional,
                 )
             };
-            let mut child = &mut self.nodes[ed.1];
+            let child = &mut self.nodes[ed.1];
 
             // If the child depth isn't set yet, set it based on the parent depth.
             if child.depth.is_none() {
                 child.depth = Some(parent_depth.unwrap() + 1);
             }
 
-            if let Some(dep_kinds_map) = self.root_deps_map.get(&parent_name) {
+            let root_deps = self.root_deps_map.get(&parent_name).filter(|_| roots[ed.0]);
+            if let Some(dep_kinds_map) = root_deps {
                 // If this is an edge from the root node,
                 // set the kind based on how the dependency is declared in the manifest file.
                 if let Some(kinds) = dep_kinds_map.get(&child.name) {
@@ -244,56 +470,45 @@ impl DepGraph {
     /// Forces the version to be displayed on dependencies that have the same name (but a different
     /// version) as another dependency.
     pub fn show_version_on_duplicates(&mut self) {
-        // Build a list of node IDs, sorted by the name of the dependency on that node.
-        let dep_ids_sorted_by_name = {
-            let mut deps = self.nodes.iter().enumerate().collect::<Vec<_>>();
-            deps.sort_by_key(|dep| &*dep.1.name);
-            deps.iter().map(|dep| dep.0).collect::<Vec<_>>()
-        };
-
-        for (i, &dep_id_i) in dep_ids_sorted_by_name
-            .iter()
-            .enumerate()
-            .take(dep_ids_sorted_by_name.len() - 1)
-        {
-            // Find other nodes with the same name.
-            // We need to iterate one more time after the last node to handle the break.
-            for (j, &dep) in dep_ids_sorted_by_name
-                .iter()
-                .enumerate()
-                .take(dep_ids_sorted_by_name.len() + 1)
-                .skip(i + 1)
-            {
-                // Stop once we've found a node with a different name or reached the end of the
-                // list.
-                if j >= dep_ids_sorted_by_name.len()
-                    || self.nodes[dep_id_i].name != self.nodes[dep].name
-                {
-                    // If there are at least two nodes with the same name
-                    if j >= i + 2 {
-                        // Set force_write_ver = true on all nodes
-                        // from dep_ids_sorted_by_name[i] to dep_ids_sorted_by_name[j - 1].
-                        // Remember: j is pointing on the next node with a *different* name!
-                        // Remember also: i..j includes i but excludes j.
-                        for &dep_id_k in dep_ids_sorted_by_name.iter().take(j).skip(i) {
-                            self.nodes[dep_id_k].force_write_ver = true;
-                        }
-                    }
+        // Count the nodes with each name, and with each name and version.
+        let mut name_counts = HashMap::new();
+        let mut name_ver_counts = HashMap::new();
+        for dep in self.nodes.iter() {
+            *name_counts.entry(dep.name.clone()).or_insert(0) += 1;
+            *name_ver_counts
+                .entry((dep.name.clone(), dep.ver.clone()))
+                .or_insert(0) += 1;
+        }
 
-                    break;
-                }
+        for dep in self.nodes.iter_mut() {
+            if name_counts[&dep.name] > 1 {
+                dep.force_write_ver = true;
+            }
+            // Packages with the same name and version can only be told apart by their source.
+            if name_ver_counts[&(dep.name.clone(), dep.ver.clone())] > 1 {
+                dep.force_write_source = true;
             }
         }
     }
 
-    pub fn add_child(&mut self, parent: usize, dep_name: &str, dep_ver: &str) {
-        let child = self.find_or_add(dep_name, dep_ver);
+    pub fn add_child(
+        &mut self,
+        parent: usize,
+        dep_name: &str,
+        dep_ver: &str,
+        dep_source: Option<&str>,
+        dev_only: bool,
+    ) {
+        let child = self.find_or_add(dep_name, dep_ver, dep_source);
 
         if parent == child {
             return;
         }
 
         self.edges.push(Edge(parent, child));
+        if dev_only {
+            self.dev_only_edges.insert(Edge(parent, child));
+        }
 
         self.nodes[parent].children.push(child);
         self.nodes[child].parents.push(parent);
@@ -306,78 +521,148 @@ impl DepGraph {
         None
     }
 
-    pub fn find(&self, name: &str, ver: &str) -> Option<usize> {
-        for (i, d) in self.nodes.iter().enumerate() {
-            if d.name == name && d.ver == ver {
-                return Some(i);
-            }
-        }
-        None
+    /// Finds a node by name, version and source. Packages with the same name and version from
+    /// different sources, e.g. crates.io and a git fork, are different nodes.
+    pub fn find(&self, name: &str, ver: &str, source: Option<&str>) -> Option<usize> {
+        self.nodes_by_name
+            .get(name)?
+            .iter()
+            .copied()
+            .find(|&i| self.nodes[i].ver == ver && self.nodes[i].source.as_deref() == source)
     }
 
-    pub fn find_or_add(&mut self, name: &str, ver: &str) -> usize {
-        if let Some(i) = self.find(name, ver) {
+    pub fn find_or_add(&mut self, name: &str, ver: &str, source: Option<&str>) -> usize {
+        if let Some(i) = self.find(name, ver, source) {
             return i;
         }
-        self.nodes
-            .push(ResolvedDep::new(name.to_owned(), ver.to_owned()));
-        self.nodes.len() - 1
+        self.nodes.push(ResolvedDep::new(
+            name.to_owned(),
+            ver.to_owned(),
+            source.map(String::from),
+        ));
+        let i = self.nodes.len() - 1;
+        self.nodes_by_name
+            .entry(name.to_owned())
+            .or_default()
+            .push(i);
+        i
+    }
+
+    /// Checks that a node is not below the maximum depth, if specified, and is not an orphan,
+    /// unless orphans are included.
+    pub fn within_limits(&self, dep: &ResolvedDep) -> bool {
+        let within_depth = self
+            .cfg
+            .depth
+            .is_none_or(|depth| dep.depth.unwrap() <= depth);
+        let orphan = !self.cfg.include_orphans && dep.kind() == DepKind::Unknown;
+
+        within_depth && !orphan
+    }
+
+    // Gets the clusters to render, which are the subgraph, if any, followed by the named clusters,
+    // and the index of the cluster of each node. Nodes go in the first cluster that selects them.
+    fn clusters(&self) -> Result<(Vec<ClusterStyle>, Vec<Option<usize>>)> {
+        let mut clusters = vec![];
+        let mut selectors = vec![];
+        if let Some(ref crates) = self.cfg.subgraph {
+            clusters.push(ClusterStyle {
+                id: "subgraph".into(),
+                label: self.cfg.subgraph_name.clone(),
+                color: "brown".into(),
+            });
+            selectors.push(CrateSelector::parse_all(crates)?);
+        }
+        for (i, cluster) in self.clusters.iter().enumerate() {
+            clusters.push(ClusterStyle {
+                id: i.to_string(),
+                label: Some(cluster.name.clone()),
+                color: cluster
+                    .color
+                    .clone()
+                    .unwrap_or_else(|| cluster::palette_color(i).into()),
+            });
+            selectors.push(CrateSelector::parse_all(&cluster.crates)?);
+        }
+
+        let mut cluster_of = self
+            .nodes
+            .iter()
+            .map(|dep| {
+                selectors.iter().position(|selectors| {
+                    selectors
+                        .iter()
+                        .any(|selector| selector.matches(&dep.name, &dep.ver))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Automatic clusters group the remaining nodes.
+        if let Some(by) = self.cfg.cluster_by {
+            let clustered = cluster_of.iter().map(Option::is_some).collect::<Vec<_>>();
+            let groups = self.auto_clusters(by, &clustered);
+            for (i, (label, nodes)) in (self.clusters.len()..).zip(groups) {
+                for n in nodes {
+                    cluster_of[n] = Some(clusters.len());
+                }
+                clusters.push(ClusterStyle {
+                    id: i.to_string(),
+                    label: Some(label),
+                    color: cluster::palette_color(i).into(),
+                });
+            }
+        }
+
+        Ok((clusters, cluster_of))
     }
 
     pub fn render_to<W: Write>(self, output: &mut W) -> Result<()> {
         // Keep track of all added nodes.
-        let mut nodes_added = vec![];
+        let mut nodes_added = vec![false; self.nodes.len()];
+        let (clusters, cluster_of) = self.clusters()?;
 
         writeln!(output, "digraph dependencies {{")?;
 
-        // Output all non-subgraph nodes.
+        // Output all nodes that are not in a cluster.
         for (i, dep) in self.nodes.iter().enumerate() {
-            // Skip subgraph nodes, will be declared in the subgraph.
-            if let Some(sub_deps) = &self.cfg.subgraph {
-                if sub_deps.contains(&dep.name) {
-                    continue;
-                }
+            if dep.hidden {
+                continue;
             }
 
-            // Skip nodes below the maximum depth, if specified.
-            // These nodes will still be output later if specified in a subgraph.
-            if let Some(depth) = self.cfg.depth {
-                if dep.depth.unwrap() > depth {
-                    continue;
-                }
+            // Skip cluster nodes, will be declared in their cluster.
+            if cluster_of[i].is_some() {
+                continue;
             }
 
-            // Skip orphan nodes.
-            // Orphan nodes will still be output later if specified in a subgraph.
-            if !self.cfg.include_orphans {
-                if let DepKind::Unknown = dep.kind() {
-                    continue;
-                }
+            // Skip nodes below the maximum depth and orphan nodes.
+            // These nodes will still be output later if specified in a cluster.
+            if !self.within_limits(dep) {
+                continue;
             }
 
             // Add the node.
             write!(output, "\tn{}", i)?;
-            dep.label(output, &self)?;
-            nodes_added.push(i);
+            dep.label(output, &self, i)?;
+            nodes_added[i] = true;
         }
         writeln!(output)?;
 
-        // Output any subgraph nodes.
-        if let Some(sub_deps) = &self.cfg.subgraph {
-            writeln!(output, "\tsubgraph cluster_subgraph {{")?;
-            if let Some(sub_name) = &self.cfg.subgraph_name {
-                writeln!(output, "\t\tlabel=\"{}\";", sub_name)?;
+        // Output the nodes of each cluster.
+        for (c, cluster) in clusters.iter().enumerate() {
+            writeln!(output, "\tsubgraph cluster_{} {{", cluster.id)?;
+            if let Some(label) = &cluster.label {
+                writeln!(output, "\t\tlabel=\"{}\";", label.replace('"', "\\\""))?;
             }
-            writeln!(output, "\t\tcolor=brown;")?;
+            writeln!(output, "\t\tcolor={};", cluster.color)?;
             writeln!(output, "\t\tstyle=dashed;")?;
             writeln!(output)?;
 
             for (i, dep) in self.nodes.iter().enumerate() {
-                if sub_deps.contains(&dep.name) {
+                if cluster_of[i] == Some(c) && !dep.hidden {
                     write!(output, "\t\tn{}", i)?;
-                    dep.label(output, &self)?;
+                    dep.label(output, &self, i)?;
 
-                    nodes_added.push(i);
+                    nodes_added[i] = true;
                 }
             }
 
@@ -387,11 +672,15 @@ impl DepGraph {
         // Output edges.
         for ed in &self.edges {
             // Only add edges if both nodes exist in the graph.
-            if !(nodes_added.contains(&ed.0) && nodes_added.contains(&ed.1)) {
+            if !(nodes_added[ed.0] && nodes_added[ed.1]) {
                 continue;
             }
 
-            write!(output, "\t{}", ed)?;
+            if self.inverted {
+                write!(output, "\t{}", Edge(ed.1, ed.0))?;
+            } else {
+                write!(output, "\t{}", ed)?;
+            }
             ed.label(output, &self)?;
         }
 
@@ -399,18 +688,4 @@ impl DepGraph {
 
         Ok(())
     }
-
-    // TODO: make this function more efficient with memoization:
-    // ahead of time, generate a list of all dependencies (direct and indirect)
-    // for all nodes, and then this function can simply check if child is in
-    // parent's list of dependencies (or possibly remove this function
-    // entirely)
-    fn transitive_dep(&self, parent: usize, child: usize) -> bool {
-        for c in self.nodes[parent].children.iter() {
-            if *c == child || self.transitive_dep(*c, child) {
-                return true;
-            }
-        }
-        false
-    }
 }
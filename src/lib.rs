@@ -9,15 +9,34 @@
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
 
+mod closure;
+mod cluster;
 mod config;
+mod cycles;
 mod dep;
+mod diff;
+mod dominators;
+mod dupes;
 mod error;
+mod features;
+mod git;
 mod graph;
+mod lock;
+mod ops;
+mod paths;
+mod platform;
 mod project;
+mod selector;
+mod stats;
+mod unify;
 mod util;
+mod workspace;
 
+pub use cluster::{Cluster, ClusterBy};
 pub use config::Config;
+pub use diff::DiffFormat;
 pub use error::{Error, Result};
+pub use stats::StatsFormat;
 
 use graph::DepGraph;
 use project::Project;
@@ -27,18 +46,31 @@ use std::{io::BufWriter, path::Path};
 ///
 /// Pass the result of this function to `render_dep_graph` for the graphviz string.
 pub fn get_dep_graph(cfg: Config) -> Result<DepGraph> {
+    is_cargo_toml(&cfg.manifest_path)?;
+    let manifest_path = cfg.manifest_path.clone();
+    let lock_path = cfg.lock_path.clone();
+    // The project opens the git revision, if any, which the files are then looked for in.
+    let project = Project::with_config(cfg)?;
+    let rev = project.revision();
+
     // Search through parent dirs for Cargo.toml.
-    let manifest_path = &cfg.manifest_path;
-    is_cargo_toml(manifest_path)?;
-    let manifest_path = util::find_file_search_parent_dirs(manifest_path)?;
+    let manifest_path = util::find_file_search_parent_dirs(&manifest_path, rev)?
+        .ok_or_else(|| Error::ManifestNotFound(manifest_path.into()))?;
 
-    // Cargo.lock must be in the same directory as Cargo.toml or in a parent directory.
-    let manifest = manifest_path.to_str().unwrap();
-    let lock_file = format!("{}.lock", &manifest[0..manifest.len() - 5]);
-    let lock_path = util::find_file_search_parent_dirs(&lock_file)?;
+    // Cargo.lock must be in the same directory as Cargo.toml or in a parent directory, unless
+    // another one is given.
+    let lock_path = match lock_path {
+        Some(lock_path) if util::is_file_at(Path::new(&lock_path), rev) => lock_path.into(),
+        Some(lock_path) => return Err(Error::LockfileNotFound(lock_path.into())),
+        None => {
+            let manifest = manifest_path.to_str().unwrap();
+            let lock_file = format!("{}.lock", &manifest[0..manifest.len() - 5]);
+            util::find_file_search_parent_dirs(&lock_file, rev)?
+                .ok_or_else(|| Error::LockfileNotFound(lock_file.into()))?
+        }
+    };
 
     // Graph the project.
-    let project = Project::with_config(cfg)?;
     project.graph(manifest_path, lock_path)
 }
 
@@ -58,12 +90,14 @@ fn is_cargo_toml(file_name: &str) -> Result<()> {
 
     if let Some(file_name) = path.file_name() {
         if file_name != "Cargo.toml" {
-            return Err(Error::Toml(
+            return Err(Error::InvalidConfig(
                 "The manifest-path must be a path to a Cargo.toml file".into(),
             ));
         }
     } else {
-        return Err(Error::Toml("The manifest path is not a valid file".into()));
+        return Err(Error::InvalidConfig(
+            "The manifest path is not a valid file".into(),
+        ));
     }
 
     Ok(())
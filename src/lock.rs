@@ -0,0 +1,223 @@
+use crate::{
+    error::{Error, Result},
+    util,
+};
+use std::{collections::HashMap, path::Path};
+use toml::Value;
+
+/// A package entry of a lock file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LockPackage {
+    pub name: String,
+    pub ver: String,
+    /// The source of the package, e.g. `registry+https://...` or `git+https://...#rev`. Path
+    /// dependencies and workspace members have no source.
+    pub source: Option<String>,
+    pub checksum: Option<String>,
+    /// Indices of the dependencies of this package in `Lockfile::packages`.
+    pub deps: Vec<usize>,
+}
+
+/// A parsed Cargo.lock file.
+#[derive(Clone, Debug)]
+pub struct Lockfile {
+    /// The format version: 1 for lock files with a [metadata] table of checksums, 2 for the
+    /// format without a version key, and the value of the `version` key from 3 onwards.
+    pub version: u32,
+    pub packages: Vec<LockPackage>,
+}
+
+impl Lockfile {
+    /// Reads and parses a Cargo.lock file.
+    pub fn from_file<P: AsRef<Path>>(lock_path: P) -> Result<Self> {
+        let lock_toml = util::toml_from_file(&lock_path)?;
+        Self::from_toml(&lock_toml, lock_path.as_ref())
+    }
+
+    /// Parses a Cargo.lock file that has been read as TOML. The path is only used in errors.
+    pub fn from_toml(lock_toml: &Value, lock_path: &Path) -> Result<Self> {
+        let invalid = |package: Option<(usize, Option<String>)>, key: &str, message: String| {
+            Error::InvalidFile {
+                path: lock_path.to_path_buf(),
+                package,
+                key: Some(key.to_string()),
+                message,
+            }
+        };
+
+        let package_tomls = if let Some(Value::Array(packages)) = lock_toml.get("package") {
+            packages.iter().collect::<Vec<_>>()
+        } else if let Some(root) = lock_toml.get("root") {
+            println!(
+                "Warning: deprecated [root] table found in lock file. Using [root] as [package]."
+            );
+
+            vec![root]
+        } else {
+            return Err(invalid(None, "package", "no [[package]] entries".into()));
+        };
+
+        // Version 1 lock files store checksums in [metadata], keyed by the full dependency
+        // reference.
+        let metadata_checksums = lock_toml
+            .get("metadata")
+            .and_then(Value::as_table)
+            .map(|metadata| {
+                metadata
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        Some((key.strip_prefix("checksum ")?.to_string(), value.as_str()?))
+                    })
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+
+        let version = match lock_toml.get("version") {
+            Some(Value::Integer(version)) if *version > 0 => *version as u32,
+            Some(_) => {
+                return Err(invalid(
+                    None,
+                    "version",
+                    "must be a positive integer".into(),
+                ))
+            }
+            None if !metadata_checksums.is_empty() => 1,
+            None => 2,
+        };
+
+        let mut packages = vec![];
+        for (i, pkg) in package_tomls.iter().enumerate() {
+            let field = |key| {
+                optional_string_field(pkg, key).map_err(|message| {
+                    let name = optional_string_field(pkg, "name").ok().flatten();
+                    invalid(Some((i, name)), key, message)
+                })
+            };
+            let required_field = |key| {
+                field(key)?.ok_or_else(|| {
+                    let name = optional_string_field(pkg, "name").ok().flatten();
+                    invalid(Some((i, name)), key, "missing field".into())
+                })
+            };
+
+            let name = required_field("name")?;
+            let ver = required_field("version")?;
+            let source = field("source")?;
+            let checksum = match field("checksum")? {
+                Some(checksum) => Some(checksum),
+                None => metadata_checksums
+                    .get(&reference(&name, &ver, source.as_deref()))
+                    .map(|checksum| checksum.to_string()),
+            };
+
+            packages.push(LockPackage {
+                name,
+                ver,
+                source,
+                checksum,
+                deps: vec![],
+            });
+        }
+
+        // Resolve the dependency references now that all packages are known.
+        let mut packages_by_name = HashMap::<_, Vec<_>>::new();
+        for (i, pkg) in packages.iter().enumerate() {
+            packages_by_name
+                .entry(pkg.name.clone())
+                .or_default()
+                .push(i);
+        }
+        for (i, pkg) in package_tomls.iter().enumerate() {
+            let location = Some((i, Some(packages[i].name.clone())));
+            let deps = match pkg.get("dependencies") {
+                None => continue,
+                Some(Value::Array(deps)) => deps,
+                Some(_) => {
+                    return Err(invalid(
+                        location,
+                        "dependencies",
+                        "expected an array".into(),
+                    ))
+                }
+            };
+
+            for dep in deps {
+                let dep = dep.as_str().ok_or_else(|| {
+                    invalid(
+                        location.clone(),
+                        "dependencies",
+                        format!("expected a string, found {}", dep.type_str()),
+                    )
+                })?;
+                let dep = resolve(&packages, &packages_by_name, dep)
+                    .map_err(|message| invalid(location.clone(), "dependencies", message))?;
+                packages[i].deps.push(dep);
+            }
+        }
+
+        Ok(Self { version, packages })
+    }
+}
+
+// Formats a package reference the way version 1 lock files do: `name version (source)`.
+fn reference(name: &str, ver: &str, source: Option<&str>) -> String {
+    match source {
+        Some(source) => format!("{} {} ({})", name, ver, source),
+        None => format!("{} {}", name, ver),
+    }
+}
+
+// Resolves a dependency reference of the form `name [version] [(source)]` against the package
+// list. Version 2 and later lock files only include the version and source when they are needed to
+// disambiguate between packages with the same name.
+fn resolve(
+    packages: &[LockPackage],
+    packages_by_name: &HashMap<String, Vec<usize>>,
+    dep: &str,
+) -> std::result::Result<usize, String> {
+    let mut parts = dep.splitn(2, ' ');
+    let name = parts.next().unwrap_or_default();
+    let rest = parts.next().unwrap_or_default().trim();
+
+    let (ver, source) = match rest.find('(') {
+        Some(i) if rest.ends_with(')') => (rest[..i].trim(), Some(&rest[i + 1..rest.len() - 1])),
+        _ => (rest, None),
+    };
+
+    let candidates = packages_by_name
+        .get(name)
+        .into_iter()
+        .flatten()
+        .copied()
+        .filter(|&i| {
+            (ver.is_empty() || packages[i].ver == ver)
+                && (source.is_none() || packages[i].source.as_deref() == source)
+        })
+        .collect::<Vec<_>>();
+
+    // Version 1 lock files omit the source of path dependencies.
+    let candidates = match candidates
+        .iter()
+        .copied()
+        .filter(|&i| packages[i].source.is_none())
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        &[i] if source.is_none() => vec![i],
+        _ => candidates,
+    };
+
+    match candidates.as_slice() {
+        [i] => Ok(*i),
+        [] => Err(format!("'{}' does not match any package", dep)),
+        _ => Err(format!("'{}' matches more than one package", dep)),
+    }
+}
+
+fn optional_string_field(pkg: &Value, key: &str) -> std::result::Result<Option<String>, String> {
+    match pkg.get(key) {
+        None => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(value) => Err(format!("expected a string, found {}", value.type_str())),
+    }
+}
@@ -12,13 +12,17 @@ use std::{
     str::FromStr,
 };
 
-use cargo_deps::{get_dep_graph, render_dep_graph, Config, Result};
+use cargo_deps::{get_dep_graph, render_dep_graph, Config, DiffFormat, Error, Result, StatsFormat};
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
 const USAGE: &str = "\
 cargo-deps writes a graph in dot format to standard output.
 
-    Typical usage is `cargo deps | dot -Tpng > graph.png`.";
+    Typical usage is `cargo deps | dot -Tpng > graph.png`.
+
+    Options that take crates accept exact names, globs such as `tokio-*` and regular expressions
+    such as `re:^aws-(sdk|smithy)-`, each optionally followed by a version requirement, e.g.
+    `serde@1` or `syn@>=1.0.60, <2`.";
 
 // TODO: remove this and uncomment the next occurrence.
 #[rustfmt::skip]
@@ -37,10 +41,17 @@ fn parse_cli<'a>() -> ArgMatches<'a> {
                     // #[rustfmt::skip]
                     "
                     -o --dot-file [PATH] 'Output file, or stdout if not specified'
+                       --features [FEATURES] ... 'Space or comma separated list of features to \
+                                                  activate. Optional deps enabled by the \
+                                                  activated features are shown (red)'
+                       --all-features 'Activate all available features'
+                       --no-default-features 'Do not activate the `default` feature'
                        --filter [DEPNAMES] ... 'Only display provided deps'
                        --include-orphans 'Don't purge orphan nodes (yellow). \
                                           This is useful in some workspaces'
                     -I --include-versions 'Include the dependency version on nodes'
+                       --invert [CRATE] 'Show the crates that depend on a crate, with edges \
+                                         pointing to the dependents'
                        --subgraph [DEPNAMES] ... 'Group provided deps in their own subgraph'
 
                       --all-deps 'Include all dependencies in the graph. \
@@ -51,6 +62,8 @@ fn parse_cli<'a>() -> ArgMatches<'a> {
                        --optional-deps 'Include optional dependencies in the graph (red)'
                        --no-transitive-deps 'Filter out edges that point to a transitive \
                                              dependency'
+                       --allow-cycles 'Render the graph even if it has cycles, which are \
+                                       highlighted (bold red)'
                     ",
                 )
                 .args(&[
@@ -62,9 +75,112 @@ fn parse_cli<'a>() -> ArgMatches<'a> {
                         ),
                     Arg::from_usage("--manifest-path [PATH] 'Specify location of manifest file'")
                         .default_value("Cargo.toml"),
+                    Arg::from_usage("--rev [REV] 'Read the manifests and the lock file as they were \
+                                                  at a git revision, e.g. HEAD~1 or a tag, without \
+                                                  touching the working tree'"),
                     Arg::from_usage("--subgraph-name [NAME] 'Optional name of subgraph'")
                         .requires("subgraph"),
-                ]),
+                    Arg::from_usage("--dominators 'Show the dominator tree: each crate points to \
+                                                   the crates that only it leads to, and is \
+                                                   labelled with how many there are'")
+                        .conflicts_with("invert"),
+                    Arg::from_usage("--focus [CRATE]... 'Only display the provided crate, its \
+                                                      dependencies and its dependents. Can be \
+                                                      repeated'")
+                        .number_of_values(1),
+                    Arg::from_usage("--prune [CRATE]... 'Hide the provided crate and the \
+                                                      dependencies that only it leads to. Can be \
+                                                      repeated'")
+                        .number_of_values(1),
+                    Arg::from_usage("--exclude [CRATE]... 'Hide the provided crate, but not its \
+                                                        dependencies. Can be repeated'")
+                        .number_of_values(1),
+                    Arg::from_usage("--cluster [NAME=CRATES]... 'Group crates in a cluster with its \
+                                                               own label and color. Can be \
+                                                               repeated, also with the same NAME'")
+                        .number_of_values(1),
+                    Arg::from_usage("--cluster-by [MODE] 'Group the crates that are not in a \
+                                                          cluster by the workspace member that \
+                                                          only uses them, by source, or by name \
+                                                          prefix'")
+                        .possible_values(&["member", "source", "prefix"]),
+                    Arg::from_usage("--target [TRIPLE] 'Only include dependencies that are \
+                                                        compiled for the given target triple'"),
+                    Arg::from_usage("--cfg [SPEC]... 'Custom cfg flag, e.g. `foo` or \
+                                                      `foo=\"bar\"`, used when evaluating \
+                                                      cfg() expressions for --target'")
+                        .number_of_values(1)
+                        .requires("target"),
+                ])
+                .subcommand(
+                    SubCommand::with_name("why")
+                        .about("Lists the paths from the root crates to a crate. Options for the \
+                                graph go before `why`, e.g. `cargo deps --dev-deps why log`")
+                        .args_from_usage(
+                            "
+                            <CRATE> 'The crate to look for'
+                            --dot 'Output the graph of all paths in dot format instead'
+                            ",
+                        )
+                        .arg(
+                            Arg::from_usage("--limit [N] 'The maximum number of paths to list. If \
+                                                          there are more, they are counted and a \
+                                                          sample is listed'")
+                                .default_value("100")
+                                .validator(|v| usize::from_str(&v)
+                                           .map(|_| ())
+                                           .map_err(|e| format!("'{}': {}", v, e))
+                                ),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("dupes")
+                        .about("Lists the crates that are in the graph in several versions, with \
+                                the crates that require each version. Options for the graph go \
+                                before `dupes`, e.g. `cargo deps --dev-deps dupes`")
+                        .args_from_usage(
+                            "--fail-on-duplicates 'Exit with code 7 if a crate is in the graph in \
+                                                   several versions'",
+                        )
+                        .arg(
+                            Arg::from_usage("--limit [N] 'The maximum number of paths from the \
+                                                          root crates to list for each version'")
+                                .default_value("3")
+                                .validator(|v| usize::from_str(&v)
+                                           .map(|_| ())
+                                           .map_err(|e| format!("'{}': {}", v, e))
+                                ),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("stats")
+                        .about("Lists how many crates each direct dependency of the root crates \
+                                pulls in, and the fan-in and fan-out of every crate. Options for \
+                                the graph go before `stats`, e.g. `cargo deps --dev-deps stats`")
+                        .arg(
+                            Arg::from_usage("--format [FORMAT] 'The format of the report'")
+                                .possible_values(&["table", "csv", "json"])
+                                .default_value("table"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("diff")
+                        .about("Lists the crates and dependencies that were added, removed, \
+                                upgraded or downgraded between two lock files. Options for the \
+                                graph go before `diff`, e.g. `cargo deps --dev-deps diff --old \
+                                main`")
+                        .args(&[
+                            Arg::from_usage("--old <LOCK|REV> 'The old lock file, or a git \
+                                                              revision to read the manifests and \
+                                                              the lock file from, e.g. HEAD~1 or \
+                                                              main'"),
+                            Arg::from_usage("--new [LOCK] 'The new lock file. The default is the \
+                                                           lock file of the manifest'"),
+                            Arg::from_usage("--format [FORMAT] 'The format of the report'")
+                                .possible_values(&["text", "markdown", "dot"])
+                                .default_value("text"),
+                        ]),
+                ),
         )
         .get_matches()
 }
@@ -78,19 +194,123 @@ fn real_main() -> Result<()> {
     let args = parse_cli();
 
     if let Some(args) = args.subcommand_matches("deps") {
-        let cfg = Config::from_matches(&args)?;
+        let cfg = Config::from_matches(args)?;
         let dot_file = cfg.dot_file.clone();
 
-        // Get dependency graph & render it.
-        let out = get_dep_graph(cfg).and_then(render_dep_graph)?;
+        // Get dependency graph & render it. A report can also fail the run once it is written.
+        let (out, failure) = match args.subcommand() {
+            ("why", Some(why_args)) => (why(cfg, why_args)?, None),
+            ("dupes", Some(dupes_args)) => dupes(cfg, dupes_args)?,
+            ("stats", Some(stats_args)) => (stats(cfg, stats_args)?, None),
+            ("diff", Some(diff_args)) => (diff(cfg, diff_args)?, None),
+            _ => (render(cfg)?, None),
+        };
 
         // Output to stdout or render the dot file.
         match dot_file {
             None => Box::new(io::stdout()) as Box<dyn Write>,
-            Some(file) => Box::new(File::create(&Path::new(&file))?),
+            Some(file) => Box::new(File::create(Path::new(&file))?),
         }
         .write_all(&out.into_bytes())?;
+
+        if let Some(failure) = failure {
+            return Err(failure);
+        }
     }
 
     Ok(())
 }
+
+// Renders the whole dependency graph.
+fn render(cfg: Config) -> Result<String> {
+    let graph = get_dep_graph(cfg)?;
+    warn_cycles(&graph.describe_cycles());
+    render_dep_graph(graph)
+}
+
+// Lists the paths to a crate, or renders the graph of the paths with --dot.
+fn why(cfg: Config, args: &ArgMatches) -> Result<String> {
+    let krate = args.value_of("CRATE").unwrap();
+    let mut graph = get_dep_graph(cfg)?;
+    warn_cycles(&graph.describe_cycles());
+
+    if args.is_present("dot") {
+        graph.retain_paths_to(krate)?;
+        render_dep_graph(graph)
+    } else {
+        let limit = usize::from_str(args.value_of("limit").unwrap()).unwrap();
+        let paths = graph.paths_to(krate, limit)?;
+        let mut out = vec![];
+        graph.write_paths(&paths, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+}
+
+// Lists the duplicated crates, along with the error to fail with if --fail-on-duplicates is given
+// and there are any.
+fn dupes(cfg: Config, args: &ArgMatches) -> Result<(String, Option<Error>)> {
+    let limit = usize::from_str(args.value_of("limit").unwrap()).unwrap();
+    let graph = get_dep_graph(cfg)?;
+    warn_cycles(&graph.describe_cycles());
+    let duplicates = graph.duplicates(limit)?;
+    let mut out = vec![];
+    graph.write_duplicates(&duplicates, &mut out)?;
+
+    let failure = if args.is_present("fail-on-duplicates") && !duplicates.is_empty() {
+        Some(Error::DuplicatesFound {
+            crates: duplicates.into_iter().map(|d| d.name).collect(),
+        })
+    } else {
+        None
+    };
+
+    Ok((String::from_utf8(out).unwrap(), failure))
+}
+
+// Writes the statistics of each crate in the chosen format.
+fn stats(cfg: Config, args: &ArgMatches) -> Result<String> {
+    let format = StatsFormat::from_str(args.value_of("format").unwrap())?;
+    let graph = get_dep_graph(cfg)?;
+    warn_cycles(&graph.describe_cycles());
+    let mut out = vec![];
+    graph.write_stats(&graph.stats(), format, &mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+// Compares the graph of the old lock file, or of the project at a git revision, with the graph of
+// the new one.
+fn diff(cfg: Config, args: &ArgMatches) -> Result<String> {
+    let format = DiffFormat::from_str(args.value_of("format").unwrap())?;
+    let old = args.value_of("old").unwrap();
+    let old_cfg = if Path::new(old).is_file() {
+        Config {
+            lock_path: Some(old.into()),
+            rev: None,
+            ..cfg.clone()
+        }
+    } else {
+        Config {
+            rev: Some(old.into()),
+            ..cfg.clone()
+        }
+    };
+    let new_cfg = Config {
+        lock_path: args.value_of("new").map(String::from),
+        ..cfg
+    };
+
+    let old = get_dep_graph(old_cfg)?;
+    let new = get_dep_graph(new_cfg)?;
+    warn_cycles(&old.describe_cycles());
+    warn_cycles(&new.describe_cycles());
+    let mut out = vec![];
+    new.write_diff(&old, &new.diff(&old), format, &mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+// Warns about the cycles that --allow-cycles let through, which are highlighted in the graph.
+fn warn_cycles(cycles: &[String]) {
+    for cycle in cycles {
+        eprintln!("Warning: cycle in dependency graph: {}", cycle);
+    }
+}
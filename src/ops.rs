@@ -0,0 +1,233 @@
+use crate::{
+    error::Result,
+    graph::{DepGraph, Edge, Node},
+    selector::CrateSelector,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+impl DepGraph {
+    /// Keeps only the selected crates. Selectors that match no crate are ignored. A kept crate that
+    /// depends on another only through crates that are not kept gets a contracted edge to it,
+    /// labelled with the number of crates it stands for, so that the filtered graph still shows
+    /// which crates reach which.
+    pub fn filter(&mut self, crates: &[&str]) -> Result<()> {
+        let selectors = CrateSelector::parse_all(crates)?;
+        let keep = self
+            .nodes
+            .iter()
+            .map(|dep| {
+                selectors
+                    .iter()
+                    .any(|selector| selector.matches(&dep.name, &dep.ver))
+            })
+            .collect::<Vec<_>>();
+        let children = self.adjacency(false);
+
+        // Edges are grouped by parent in topological order, which the contracted edges follow.
+        let mut parents = vec![];
+        let mut seen = vec![false; self.nodes.len()];
+        for &Edge(parent, _) in self.edges.iter() {
+            if !seen[parent] {
+                seen[parent] = true;
+                parents.push(parent);
+            }
+        }
+        let mut edges_of = HashMap::new();
+        for &edge in self.edges.iter() {
+            edges_of.entry(edge.0).or_insert_with(Vec::new).push(edge);
+        }
+
+        let mut edges = vec![];
+        for &parent in parents.iter().filter(|&&n| keep[n]) {
+            let direct = edges_of.remove(&parent).unwrap_or_default();
+            let mut contracted = contract(&children, &keep, parent)
+                .into_iter()
+                .filter(|&(child, _)| child != parent && !direct.contains(&Edge(parent, child)))
+                .collect::<Vec<_>>();
+            contracted.sort_unstable();
+
+            edges.extend(direct.into_iter().filter(|edge| keep[edge.1]));
+            for (child, via) in contracted {
+                self.contracted_edges.insert(Edge(parent, child), via);
+                edges.push(Edge(parent, child));
+            }
+        }
+        self.edges = edges;
+
+        for (dep, &keep) in self.nodes.iter_mut().zip(keep.iter()) {
+            dep.hidden |= !keep;
+        }
+
+        Ok(())
+    }
+
+    /// Turns the graph into the reverse-dependency graph of the crates selected by `krate`, a
+    /// `CrateSelector`: only those crates and the crates that depend on them, directly or not, are
+    /// kept, and edges are rendered from each crate to its dependents. The depth of each crate
+    /// becomes its distance from the nearest inverted crate, so that `depth` limits how many levels
+    /// of dependents are shown.
+    pub fn invert(&mut self, krate: &str) -> Result<()> {
+        let targets = self.find_crates(&[krate])?;
+        let parents = self.adjacency(true);
+
+        // Walk up from the crate, breadth-first so that each crate gets its shortest distance.
+        let mut depths = vec![None; self.nodes.len()];
+        let mut queue = VecDeque::new();
+        for n in (0..self.nodes.len()).filter(|&n| targets[n]) {
+            depths[n] = Some(0);
+            queue.push_back(n);
+        }
+        while let Some(n) = queue.pop_front() {
+            for &parent in parents[n].iter() {
+                if depths[parent].is_none() {
+                    depths[parent] = depths[n].map(|depth| depth + 1);
+                    queue.push_back(parent);
+                }
+            }
+        }
+
+        for (dep, depth) in self.nodes.iter_mut().zip(depths.iter()) {
+            dep.depth = *depth;
+        }
+        let keep = depths.iter().map(Option::is_some).collect::<Vec<_>>();
+        self.retain_nodes(&keep);
+        self.inverted = true;
+
+        Ok(())
+    }
+
+    /// Keeps only the given crates, the crates they depend on and the crates that depend on them,
+    /// directly or not.
+    pub fn focus(&mut self, crates: &[&str]) -> Result<()> {
+        let targets = self.find_crates(crates)?;
+        let descendants = reachable(&self.adjacency(false), &targets, &[]);
+        let ancestors = reachable(&self.adjacency(true), &targets, &[]);
+
+        let keep = descendants
+            .iter()
+            .zip(ancestors.iter())
+            .map(|(&down, &up)| down || up)
+            .collect::<Vec<_>>();
+        self.retain_nodes(&keep);
+
+        Ok(())
+    }
+
+    /// Removes the given crates and every crate that can then no longer be reached from a root
+    /// crate. Crates that the removed crates depend on are kept if another path still leads to them.
+    pub fn prune(&mut self, crates: &[&str]) -> Result<()> {
+        let pruned = self.find_crates(crates)?;
+        let roots = (0..self.nodes.len())
+            .map(|n| self.is_root(n) && !self.nodes[n].hidden)
+            .collect::<Vec<_>>();
+
+        let keep = reachable(&self.adjacency(false), &roots, &pruned);
+        self.retain_nodes(&keep);
+
+        Ok(())
+    }
+
+    /// Removes the given crates, but not the crates they depend on.
+    pub fn exclude(&mut self, crates: &[&str]) -> Result<()> {
+        let excluded = self.find_crates(crates)?;
+
+        let keep = excluded
+            .iter()
+            .map(|&excluded| !excluded)
+            .collect::<Vec<_>>();
+        self.retain_nodes(&keep);
+
+        Ok(())
+    }
+
+    // Marks the nodes of the crates, each given as a `CrateSelector`.
+    fn find_crates(&self, crates: &[&str]) -> Result<Vec<bool>> {
+        self.find_targets(&CrateSelector::parse_all(crates)?)
+    }
+
+    // Builds the adjacency lists of the edges, from each node to its children, or to its parents if
+    // `reverse` is set.
+    pub(crate) fn adjacency(&self, reverse: bool) -> Vec<Vec<Node>> {
+        let mut adjacency = vec![vec![]; self.nodes.len()];
+        for &Edge(parent, child) in self.edges.iter() {
+            if reverse {
+                adjacency[child].push(parent);
+            } else {
+                adjacency[parent].push(child);
+            }
+        }
+        adjacency
+    }
+
+    // Hides the nodes that are not kept and removes their edges.
+    fn retain_nodes(&mut self, keep: &[bool]) {
+        for (dep, &keep) in self.nodes.iter_mut().zip(keep.iter()) {
+            dep.hidden |= !keep;
+        }
+        let nodes = &self.nodes;
+        self.edges
+            .retain(|edge| !nodes[edge.0].hidden && !nodes[edge.1].hidden);
+    }
+}
+
+// Finds the kept nodes that `from` reaches through at least one node that is not kept, along with
+// the number of such nodes on the paths to each of them.
+fn contract(children: &[Vec<Node>], keep: &[bool], from: Node) -> Vec<(Node, usize)> {
+    // The nodes that are not kept and can be reached from `from` without going through a kept node.
+    let mut via = HashSet::new();
+    let mut stack = children[from]
+        .iter()
+        .copied()
+        .filter(|&child| !keep[child])
+        .collect::<Vec<_>>();
+    while let Some(n) = stack.pop() {
+        if via.insert(n) {
+            stack.extend(children[n].iter().copied().filter(|&child| !keep[child]));
+        }
+    }
+
+    let mut parents_in_via: HashMap<Node, Vec<Node>> = HashMap::new();
+    let mut targets = HashSet::new();
+    for &n in via.iter() {
+        for &child in children[n].iter() {
+            parents_in_via.entry(child).or_default().push(n);
+            if keep[child] {
+                targets.insert(child);
+            }
+        }
+    }
+
+    // Count the nodes that lie on a path to each target by walking back up from it.
+    let mut contracted = vec![];
+    for target in targets {
+        let mut on_path = HashSet::new();
+        let mut stack = parents_in_via[&target].clone();
+        while let Some(n) = stack.pop() {
+            if on_path.insert(n) {
+                stack.extend(parents_in_via.get(&n).into_iter().flatten().copied());
+            }
+        }
+        contracted.push((target, on_path.len()));
+    }
+
+    contracted
+}
+
+// Marks the nodes that can be reached from the start nodes, including the start nodes, without
+// going through a blocked node.
+pub(crate) fn reachable(adjacency: &[Vec<Node>], start: &[bool], blocked: &[bool]) -> Vec<bool> {
+    let mut reached = vec![false; adjacency.len()];
+    let mut stack = (0..adjacency.len())
+        .filter(|&n| start[n])
+        .collect::<Vec<_>>();
+
+    while let Some(n) = stack.pop() {
+        if reached[n] || blocked.get(n).copied().unwrap_or(false) {
+            continue;
+        }
+        reached[n] = true;
+        stack.extend(adjacency[n].iter().copied());
+    }
+
+    reached
+}
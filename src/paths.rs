@@ -0,0 +1,285 @@
+use crate::{
+    dep::DepKind,
+    error::{Error, Result},
+    graph::{DepGraph, Edge, Node},
+    selector::CrateSelector,
+};
+use std::{collections::HashSet, io::Write};
+
+/// The paths from the root crates to a crate, as found by `DepGraph::paths_to`.
+#[derive(Clone, Debug)]
+pub struct DepPaths {
+    /// The nodes of the crate, one for each matching version.
+    pub targets: Vec<Node>,
+    /// The total number of paths, saturating at `u128::MAX`.
+    pub count: u128,
+    /// All paths, or an evenly spread sample of them if there are more than the limit.
+    pub paths: Vec<DepPath>,
+}
+
+/// A path from a root crate to a crate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepPath {
+    /// The nodes on the path, starting with the root crate.
+    pub nodes: Vec<Node>,
+    /// The kind of each hop, so that `kinds[i]` is the kind of the edge from `nodes[i]` to
+    /// `nodes[i + 1]`.
+    pub kinds: Vec<DepKind>,
+}
+
+impl DepGraph {
+    /// Finds the paths from the root crates to the crates selected by `krate`, a `CrateSelector`.
+    /// If there are more than `limit` paths, they are counted and an evenly spread sample of `limit`
+    /// paths is returned.
+    ///
+    /// Paths follow the dev-dependencies of the root crate they start from, but not those of other
+    /// crates, which Cargo does not build. Edges on allowed cycles are not followed.
+    pub fn paths_to(&self, krate: &str, limit: usize) -> Result<DepPaths> {
+        let targets = self.find_targets(&[CrateSelector::parse(krate)?])?;
+        self.paths_to_targets(&targets, limit)
+    }
+
+    // Finds the paths to the marked nodes, as for `paths_to`.
+    pub(crate) fn paths_to_targets(&self, targets: &[bool], limit: usize) -> Result<DepPaths> {
+        let counts = self.path_counts(targets);
+
+        // Paths are numbered in depth-first order, starting with the first root by name.
+        let mut roots = (0..self.nodes.len())
+            .filter(|&n| self.is_root(n))
+            .map(|n| (n, self.root_path_count(n, targets, &counts)))
+            .filter(|&(_, count)| count > 0)
+            .collect::<Vec<_>>();
+        roots.sort_by_key(|&(n, _)| (&self.nodes[n].name, &self.nodes[n].ver));
+        let count = roots
+            .iter()
+            .fold(0u128, |total, &(_, count)| total.saturating_add(count));
+
+        let mut paths = vec![];
+        let sample_size = if count > limit as u128 {
+            limit as u128
+        } else {
+            count
+        };
+        for i in 0..sample_size {
+            // Spread the sample evenly over all paths.
+            let mut index = i * (count / sample_size);
+            for &(root, root_count) in roots.iter() {
+                if index < root_count {
+                    paths.push(self.unrank_path(root, index, targets, &counts)?);
+                    break;
+                }
+                index -= root_count;
+            }
+        }
+
+        Ok(DepPaths {
+            targets: (0..self.nodes.len()).filter(|&n| targets[n]).collect(),
+            count,
+            paths,
+        })
+    }
+
+    /// Hides every node and removes every edge that is not on a path from a root crate to the crates
+    /// selected by `krate`. See `paths_to` for the edges that are followed.
+    pub fn retain_paths_to(&mut self, krate: &str) -> Result<()> {
+        let targets = self.find_targets(&[CrateSelector::parse(krate)?])?;
+        let counts = self.path_counts(&targets);
+
+        // Walk down from the roots, only following hops that lead to the crate.
+        let mut on_path = vec![false; self.nodes.len()];
+        let mut edges = HashSet::new();
+        let mut stack = vec![];
+        let roots = (0..self.nodes.len())
+            .filter(|&n| self.is_root(n) && self.root_path_count(n, &targets, &counts) > 0)
+            .collect::<Vec<_>>();
+        for &root in roots.iter() {
+            on_path[root] = true;
+        }
+        for root in roots {
+            for child in self.hops(root, true) {
+                if counts[child] > 0 {
+                    edges.insert(Edge(root, child));
+                    stack.push(child);
+                }
+            }
+        }
+        while let Some(n) = stack.pop() {
+            if on_path[n] {
+                continue;
+            }
+            on_path[n] = true;
+            for child in self.hops(n, false) {
+                if counts[child] > 0 {
+                    edges.insert(Edge(n, child));
+                    stack.push(child);
+                }
+            }
+        }
+
+        for (n, dep) in self.nodes.iter_mut().enumerate() {
+            dep.hidden |= !on_path[n];
+        }
+        self.edges.retain(|edge| edges.contains(edge));
+
+        Ok(())
+    }
+
+    /// Writes one line per path, e.g. `app v0.1.0 -> [regular] log v0.4.0`.
+    pub fn write_paths<W: Write>(&self, paths: &DepPaths, w: &mut W) -> Result<()> {
+        let targets = paths
+            .targets
+            .iter()
+            .map(|&n| format!("{} v{}", self.nodes[n].name, self.nodes[n].ver))
+            .collect::<Vec<_>>()
+            .join(", ");
+        match paths.count {
+            1 => writeln!(w, "1 path to {}:", targets)?,
+            count if count == paths.paths.len() as u128 => {
+                writeln!(w, "{} paths to {}:", count, targets)?
+            }
+            count => writeln!(
+                w,
+                "{} paths to {}, showing {}:",
+                count,
+                targets,
+                paths.paths.len()
+            )?,
+        }
+
+        for path in paths.paths.iter() {
+            let root = &self.nodes[path.nodes[0]];
+            write!(w, "{} v{}", root.name, root.ver)?;
+            for (&n, kind) in path.nodes[1..].iter().zip(path.kinds.iter()) {
+                write!(
+                    w,
+                    " -> [{}] {} v{}",
+                    kind, self.nodes[n].name, self.nodes[n].ver
+                )?;
+            }
+            writeln!(w)?;
+        }
+
+        Ok(())
+    }
+
+    // Marks the nodes selected by any of the selectors. Fails if a selector matches no crate.
+    pub(crate) fn find_targets(&self, selectors: &[CrateSelector]) -> Result<Vec<bool>> {
+        let mut targets = vec![false; self.nodes.len()];
+        for selector in selectors {
+            let mut found = false;
+            for (target, dep) in targets.iter_mut().zip(self.nodes.iter()) {
+                if selector.matches(&dep.name, &dep.ver) {
+                    *target = true;
+                    found = true;
+                }
+            }
+            if !found {
+                return Err(Error::InvalidConfig(format!(
+                    "No crate matching '{}' is in the dependency graph",
+                    selector
+                )));
+            }
+        }
+
+        Ok(targets)
+    }
+
+    // Gets the nodes that a path can go to from `n`. Dev-only edges are only followed from the root
+    // crate that a path starts from.
+    fn hops(&self, n: Node, from_root: bool) -> impl Iterator<Item = Node> + '_ {
+        self.nodes[n]
+            .children
+            .iter()
+            .copied()
+            .filter(move |&child| {
+                let edge = Edge(n, child);
+                (from_root || !self.dev_only_edges.contains(&edge))
+                    && !self.cycle_edges.contains(&edge)
+            })
+    }
+
+    // Counts the paths from each node to the targets, not counting paths that start with a
+    // dev-only edge. Without dev-only edges and cycle edges the graph has no cycles, so the counts
+    // can be computed bottom-up with a depth-first search.
+    fn path_counts(&self, targets: &[bool]) -> Vec<u128> {
+        let mut counts: Vec<Option<u128>> = vec![None; self.nodes.len()];
+
+        for start in 0..self.nodes.len() {
+            let mut stack = vec![(start, false)];
+            while let Some((n, children_done)) = stack.pop() {
+                if counts[n].is_some() {
+                    continue;
+                }
+
+                if children_done {
+                    let count = self
+                        .hops(n, false)
+                        .fold(targets[n] as u128, |count, child| {
+                            count.saturating_add(counts[child].unwrap())
+                        });
+                    counts[n] = Some(count);
+                } else {
+                    stack.push((n, true));
+                    stack.extend(
+                        self.hops(n, false)
+                            .filter(|&child| counts[child].is_none())
+                            .map(|child| (child, false)),
+                    );
+                }
+            }
+        }
+
+        counts.into_iter().map(Option::unwrap).collect()
+    }
+
+    // Counts the paths from a root crate, including those through its dev-dependencies.
+    fn root_path_count(&self, root: Node, targets: &[bool], counts: &[u128]) -> u128 {
+        self.hops(root, true)
+            .fold(targets[root] as u128, |count, child| {
+                count.saturating_add(counts[child])
+            })
+    }
+
+    // Builds the path with the given index among the paths from `root`. At each node, the path
+    // that ends there comes first, followed by the paths through each child in order.
+    fn unrank_path(
+        &self,
+        root: Node,
+        mut index: u128,
+        targets: &[bool],
+        counts: &[u128],
+    ) -> Result<DepPath> {
+        let mut path = DepPath {
+            nodes: vec![root],
+            kinds: vec![],
+        };
+        let mut n = root;
+
+        loop {
+            if targets[n] {
+                if index == 0 {
+                    return Ok(path);
+                }
+                index -= 1;
+            }
+
+            let mut next = None;
+            for child in self.hops(n, path.nodes.len() == 1) {
+                if index < counts[child] {
+                    next = Some(child);
+                    break;
+                }
+                index -= counts[child];
+            }
+            // Only counts that saturated can run past the last path.
+            let child = match next {
+                Some(child) => child,
+                None => return Ok(path),
+            };
+
+            path.kinds.push(self.edge_kind(n, child)?);
+            path.nodes.push(child);
+            n = child;
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use crate::error::{Error, Result};
+use std::{iter::Peekable, str::CharIndices};
+
+// Built-in target information: (triple, arch, os, family, env, vendor, pointer width, endian,
+// panic strategy, widest atomic).
+type TargetInfo = (
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static [&'static str],
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+    u32,
+);
+
+#[rustfmt::skip]
+const TARGETS: &[TargetInfo] = &[
+    ("aarch64-apple-darwin", "aarch64", "macos", &["unix"], "", "apple", "64", "little", "unwind", 128),
+    ("aarch64-apple-ios", "aarch64", "ios", &["unix"], "", "apple", "64", "little", "unwind", 128),
+    ("aarch64-linux-android", "aarch64", "android", &["unix"], "", "unknown", "64", "little", "unwind", 128),
+    ("aarch64-pc-windows-msvc", "aarch64", "windows", &["windows"], "msvc", "pc", "64", "little", "unwind", 128),
+    ("aarch64-unknown-linux-gnu", "aarch64", "linux", &["unix"], "gnu", "unknown", "64", "little", "unwind", 128),
+    ("aarch64-unknown-linux-musl", "aarch64", "linux", &["unix"], "musl", "unknown", "64", "little", "unwind", 128),
+    ("arm-unknown-linux-gnueabihf", "arm", "linux", &["unix"], "gnu", "unknown", "32", "little", "unwind", 64),
+    ("armv7-linux-androideabi", "arm", "android", &["unix"], "", "unknown", "32", "little", "unwind", 64),
+    ("armv7-unknown-linux-gnueabihf", "arm", "linux", &["unix"], "gnu", "unknown", "32", "little", "unwind", 64),
+    ("i686-linux-android", "x86", "android", &["unix"], "", "unknown", "32", "little", "unwind", 64),
+    ("i686-pc-windows-gnu", "x86", "windows", &["windows"], "gnu", "pc", "32", "little", "unwind", 64),
+    ("i686-pc-windows-msvc", "x86", "windows", &["windows"], "msvc", "pc", "32", "little", "unwind", 64),
+    ("i686-unknown-linux-gnu", "x86", "linux", &["unix"], "gnu", "unknown", "32", "little", "unwind", 64),
+    ("powerpc64le-unknown-linux-gnu", "powerpc64", "linux", &["unix"], "gnu", "unknown", "64", "little", "unwind", 64),
+    ("riscv64gc-unknown-linux-gnu", "riscv64", "linux", &["unix"], "gnu", "unknown", "64", "little", "unwind", 64),
+    ("s390x-unknown-linux-gnu", "s390x", "linux", &["unix"], "gnu", "unknown", "64", "big", "unwind", 64),
+    ("thumbv7em-none-eabihf", "arm", "none", &[], "", "unknown", "32", "little", "abort", 32),
+    ("wasm32-unknown-emscripten", "wasm32", "emscripten", &["unix", "wasm"], "", "unknown", "32", "little", "unwind", 64),
+    ("wasm32-unknown-unknown", "wasm32", "unknown", &["wasm"], "", "unknown", "32", "little", "abort", 64),
+    ("wasm32-wasip1", "wasm32", "wasi", &["wasm"], "p1", "unknown", "32", "little", "abort", 64),
+    ("x86_64-apple-darwin", "x86_64", "macos", &["unix"], "", "apple", "64", "little", "unwind", 64),
+    ("x86_64-apple-ios", "x86_64", "ios", &["unix"], "", "apple", "64", "little", "unwind", 64),
+    ("x86_64-linux-android", "x86_64", "android", &["unix"], "", "unknown", "64", "little", "unwind", 64),
+    ("x86_64-pc-windows-gnu", "x86_64", "windows", &["windows"], "gnu", "pc", "64", "little", "unwind", 64),
+    ("x86_64-pc-windows-msvc", "x86_64", "windows", &["windows"], "msvc", "pc", "64", "little", "unwind", 64),
+    ("x86_64-unknown-freebsd", "x86_64", "freebsd", &["unix"], "", "unknown", "64", "little", "unwind", 64),
+    ("x86_64-unknown-illumos", "x86_64", "illumos", &["unix"], "", "unknown", "64", "little", "unwind", 64),
+    ("x86_64-unknown-linux-gnu", "x86_64", "linux", &["unix"], "gnu", "unknown", "64", "little", "unwind", 64),
+    ("x86_64-unknown-linux-musl", "x86_64", "linux", &["unix"], "musl", "unknown", "64", "little", "unwind", 64),
+    ("x86_64-unknown-netbsd", "x86_64", "netbsd", &["unix"], "", "unknown", "64", "little", "unwind", 64),
+    ("x86_64-unknown-openbsd", "x86_64", "openbsd", &["unix"], "", "unknown", "64", "little", "unwind", 64),
+];
+
+// The target features that every target of an architecture enables, e.g. `sse2` on `x86_64`.
+#[rustfmt::skip]
+const BASELINE_FEATURES: &[(&str, &[&str])] = &[
+    ("aarch64", &["neon"]),
+    ("riscv64", &["a", "c", "d", "f", "m"]),
+    ("x86", &["fxsr", "sse", "sse2"]),
+    ("x86_64", &["fxsr", "sse", "sse2"]),
+];
+
+// The atomic widths that can be set by `target_has_atomic`, in bits.
+const ATOMIC_WIDTHS: &[u32] = &[8, 16, 32, 64, 128];
+
+/// A target platform, used to decide which `[target]` dependency tables apply.
+#[derive(Clone, Debug)]
+pub struct Platform {
+    triple: String,
+    /// The cfg options that are set for this platform, e.g. `unix` or `target_os="linux"`.
+    cfgs: Vec<(String, Option<String>)>,
+}
+
+impl Platform {
+    /// Looks up the target triple in the table of built-in targets and adds any custom cfg flags,
+    /// given as `name` or `key="value"`.
+    pub fn new(triple: &str, custom_cfgs: &[String]) -> Result<Self> {
+        let &(_, arch, os, families, env, vendor, pointer_width, endian, panic, atomic_width) =
+            TARGETS
+                .iter()
+                .find(|target| target.0 == triple)
+                .ok_or_else(|| {
+                    Error::InvalidConfig(format!(
+                        "Unknown target '{}'. Known targets are: {}",
+                        triple,
+                        TARGETS
+                            .iter()
+                            .map(|target| target.0)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                })?;
+
+        let mut cfgs = vec![
+            ("target_arch".to_string(), Some(arch.to_string())),
+            ("target_os".to_string(), Some(os.to_string())),
+            ("target_env".to_string(), Some(env.to_string())),
+            ("target_vendor".to_string(), Some(vendor.to_string())),
+            (
+                "target_pointer_width".to_string(),
+                Some(pointer_width.to_string()),
+            ),
+            ("target_endian".to_string(), Some(endian.to_string())),
+            ("panic".to_string(), Some(panic.to_string())),
+        ];
+        for family in families {
+            cfgs.push(("target_family".to_string(), Some(family.to_string())));
+            // `unix` and `windows` are shorthands for the family.
+            if *family == "unix" || *family == "windows" {
+                cfgs.push((family.to_string(), None));
+            }
+        }
+        // The atomics up to the widest one are set, and every built-in target has pointer-sized ones.
+        for width in ATOMIC_WIDTHS.iter().filter(|&&width| width <= atomic_width) {
+            cfgs.push(("target_has_atomic".to_string(), Some(width.to_string())));
+        }
+        cfgs.push(("target_has_atomic".to_string(), Some("ptr".to_string())));
+        for (_, features) in BASELINE_FEATURES.iter().filter(|(a, _)| *a == arch) {
+            for feature in features.iter() {
+                cfgs.push(("target_feature".to_string(), Some(feature.to_string())));
+            }
+        }
+
+        for cfg in custom_cfgs {
+            let cfg = match cfg.find('=') {
+                Some(i) => (
+                    cfg[..i].trim().to_string(),
+                    Some(cfg[i + 1..].trim().trim_matches('"').to_string()),
+                ),
+                None => (cfg.trim().to_string(), None),
+            };
+            cfgs.push(cfg);
+        }
+
+        Ok(Self {
+            triple: triple.to_string(),
+            cfgs,
+        })
+    }
+
+    /// Checks whether the key of a `[target.<key>]` table, either a `cfg(..)` expression or a
+    /// target triple, applies to this platform.
+    pub fn matches(&self, key: &str) -> Result<bool> {
+        let key = key.trim();
+
+        if key.starts_with("cfg(") && key.ends_with(')') {
+            let expr = CfgExpr::parse(&key[4..key.len() - 1]).map_err(|err| {
+                Error::Generic(format!("Could not parse target '{}': {}", key, err))
+            })?;
+            Ok(expr.eval(&self.cfgs))
+        } else {
+            Ok(key == self.triple)
+        }
+    }
+}
+
+/// A parsed `cfg(..)` expression.
+#[derive(Clone, Debug, PartialEq)]
+enum CfgExpr {
+    Name(String),
+    KeyValue(String, String),
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Not(Box<CfgExpr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Str(&'a str),
+    LeftParen,
+    RightParen,
+    Comma,
+    Equals,
+}
+
+impl CfgExpr {
+    fn parse(s: &str) -> std::result::Result<Self, String> {
+        let tokens = tokenize(s)?;
+        let mut tokens = tokens.iter().peekable();
+        let expr = parse_expr(&mut tokens)?;
+
+        match tokens.next() {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {:?}", token)),
+        }
+    }
+
+    fn eval(&self, cfgs: &[(String, Option<String>)]) -> bool {
+        match self {
+            Self::Name(name) => cfgs.iter().any(|(k, v)| k == name && v.is_none()),
+            Self::KeyValue(key, value) => cfgs
+                .iter()
+                .any(|(k, v)| k == key && v.as_deref() == Some(value.as_str())),
+            Self::All(exprs) => exprs.iter().all(|expr| expr.eval(cfgs)),
+            Self::Any(exprs) => exprs.iter().any(|expr| expr.eval(cfgs)),
+            Self::Not(expr) => !expr.eval(cfgs),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> std::result::Result<Vec<Token<'_>>, String> {
+    let mut tokens = vec![];
+    let mut chars: Peekable<CharIndices> = s.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '(' => tokens.push(Token::LeftParen),
+            ')' => tokens.push(Token::RightParen),
+            ',' => tokens.push(Token::Comma),
+            '=' => tokens.push(Token::Equals),
+            '"' => {
+                let end = loop {
+                    match chars.next() {
+                        Some((i, '"')) => break i,
+                        Some(_) => (),
+                        None => return Err("unterminated string".into()),
+                    }
+                };
+                tokens.push(Token::Str(&s[start + 1..end]));
+            }
+            c if c.is_whitespace() => (),
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut end = s.len();
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        end = i;
+                        break;
+                    }
+                    chars.next();
+                }
+                tokens.push(Token::Ident(&s[start..end]));
+            }
+            c => return Err(format!("unexpected character '{}'", c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_expr<'a, I>(tokens: &mut Peekable<I>) -> std::result::Result<CfgExpr, String>
+where
+    I: Iterator<Item = &'a Token<'a>>,
+{
+    let name = match tokens.next() {
+        Some(Token::Ident(name)) => *name,
+        Some(token) => return Err(format!("expected identifier, found {:?}", token)),
+        None => return Err("unexpected end of expression".into()),
+    };
+
+    match tokens.peek() {
+        Some(Token::Equals) => {
+            tokens.next();
+            match tokens.next() {
+                Some(Token::Str(value)) => Ok(CfgExpr::KeyValue(name.into(), value.to_string())),
+                _ => Err(format!("expected a string after '{} ='", name)),
+            }
+        }
+        Some(Token::LeftParen) => {
+            tokens.next();
+            let mut exprs = vec![];
+            loop {
+                if let Some(Token::RightParen) = tokens.peek() {
+                    tokens.next();
+                    break;
+                }
+                exprs.push(parse_expr(tokens)?);
+                match tokens.next() {
+                    Some(Token::Comma) => (),
+                    Some(Token::RightParen) => break,
+                    _ => return Err(format!("expected ',' or ')' in '{}(..)'", name)),
+                }
+            }
+
+            match name {
+                "all" => Ok(CfgExpr::All(exprs)),
+                "any" => Ok(CfgExpr::Any(exprs)),
+                "not" if exprs.len() == 1 => Ok(CfgExpr::Not(Box::new(exprs.remove(0)))),
+                "not" => Err("'not(..)' takes exactly one argument".into()),
+                _ => Err(format!("unknown operator '{}'", name)),
+            }
+        }
+        _ => Ok(CfgExpr::Name(name.into())),
+    }
+}
@@ -1,32 +1,86 @@
 use crate::{
+    cluster,
     config::Config,
     dep::{DepKind, RootCrate},
     error::{Error, Result},
+    features::{self, EnabledDepsMap},
+    git::Revision,
     graph::DepGraph,
-    util,
+    lock::{LockPackage, Lockfile},
+    platform::Platform,
+    unify, util, workspace,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
 };
-use std::{collections::HashMap, path::PathBuf};
 use toml::Value;
 
 // Map of dep names to their kinds.
 pub type DepKindsMap = HashMap<String, Vec<DepKind>>;
 // Map of root names to dep kinds maps.
 pub type RootDepsMap = HashMap<String, DepKindsMap>;
+// Map of dep names to the [target] platforms (cfg expressions or target triples) they are declared
+// for. Only contains deps that are not also declared unconditionally.
+pub type DepPlatformsMap = HashMap<String, Vec<String>>;
+// Map of root names to dep platforms maps.
+pub type RootPlatformsMap = HashMap<String, DepPlatformsMap>;
+// Map of optional dep names to the features that enable them.
+pub type DepFeaturesMap = HashMap<String, Vec<String>>;
+// Map of root names to dep features maps.
+pub type RootFeaturesMap = HashMap<String, DepFeaturesMap>;
+// Map of dep names to the version requirements they are declared with.
+pub type DepReqsMap = HashMap<String, Vec<String>>;
+// Map of root names to dep requirements maps.
+pub type RootReqsMap = HashMap<String, DepReqsMap>;
 
 #[derive(Debug)]
 pub struct Project {
     cfg: Config,
+    platform: Option<Platform>,
+    /// The git revision to read the manifests and the lock file from, if any.
+    revision: Option<Revision>,
 }
 
 impl Project {
     pub fn with_config(cfg: Config) -> Result<Self> {
-        Ok(Self { cfg })
+        let platform = match cfg.target {
+            Some(ref triple) => Some(Platform::new(
+                triple,
+                cfg.cfgs.as_deref().unwrap_or_default(),
+            )?),
+            None => None,
+        };
+        // Git is run in the directory of the manifest, which is in the repository to read from.
+        let revision = match cfg.rev {
+            Some(ref rev) => {
+                let dir = match Path::new(&cfg.manifest_path).parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => Path::new("."),
+                };
+                Some(Revision::open(rev, dir)?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            cfg,
+            platform,
+            revision,
+        })
+    }
+
+    /// The git revision that the manifests and the lock file are read from, if any.
+    pub fn revision(&self) -> Option<&Revision> {
+        self.revision.as_ref()
     }
 
     pub fn graph(self, manifest_path: PathBuf, lock_path: PathBuf) -> Result<DepGraph> {
-        let (root_crates, root_deps_map) = self.parse_root_deps(&manifest_path)?;
+        let mut dg = DepGraph::new(self.cfg.clone());
+
+        self.parse_root_deps(&manifest_path, &mut dg)?;
 
-        let mut dg = self.parse_lock_file(lock_path, &root_crates, root_deps_map)?;
+        self.parse_lock_file(lock_path, &mut dg)?;
 
         // Sort the graph.
         dg.topological_sort()?;
@@ -38,213 +92,465 @@ impl Project {
             dg.show_version_on_duplicates();
         }
 
+        // Prune before filtering, which may hide the root crates that pruning starts from, and
+        // before excluding, so that excluded crates do not cut off the crates below them.
+        if let Some(ref crates) = self.cfg.prune {
+            dg.prune(&crates.iter().map(String::as_str).collect::<Vec<_>>())?;
+        }
+        // Filter once the whole graph is known, so that paths through the crates that are filtered
+        // out can be contracted. NOTE: This will filter out root crates if they are not passed in.
+        // This is useful for e.g. workspaces if the user does not want all roots.
+        if let Some(ref names) = self.cfg.filter {
+            dg.filter(&names.iter().map(String::as_str).collect::<Vec<_>>())?;
+        }
+        if let Some(ref crates) = self.cfg.exclude {
+            dg.exclude(&crates.iter().map(String::as_str).collect::<Vec<_>>())?;
+        }
+        if let Some(ref crates) = self.cfg.focus {
+            dg.focus(&crates.iter().map(String::as_str).collect::<Vec<_>>())?;
+        }
+        if let Some(ref krate) = self.cfg.invert {
+            dg.invert(krate)?;
+        }
+        if self.cfg.dominators {
+            dg.retain_dominator_tree();
+        }
+
         Ok(dg)
     }
 
-    /// Builds a list of the dependencies declared in the manifest file.
-    pub fn parse_root_deps(
-        &self,
-        manifest_path: &PathBuf,
-    ) -> Result<(Vec<RootCrate>, RootDepsMap)> {
-        let manifest_toml = util::toml_from_file(manifest_path)?;
+    /// Builds a list of the dependencies declared in the manifest file, and stores them in the
+    /// root maps of the graph along with the root crates.
+    pub fn parse_root_deps(&self, manifest_path: &Path, dg: &mut DepGraph) -> Result<()> {
+        let rev = self.revision();
+        let mut manifest_toml = util::toml_from_file_at(manifest_path, rev)?;
+
+        // Clusters declared in the manifest can be extended from the command line.
+        dg.clusters = cluster::from_manifest(manifest_path, &manifest_toml)?;
+        cluster::merge(&mut dg.clusters, self.cfg.clusters.clone());
+        let member_manifests = workspace::member_manifests(manifest_path, &manifest_toml, rev)?;
+
+        // Find the workspace root, which may be this manifest or one in a parent directory, so that
+        // `workspace = true` fields can be resolved.
+        let workspace_root = if manifest_toml.get("workspace").is_some() {
+            Some((manifest_path.to_path_buf(), manifest_toml.clone()))
+        } else {
+            workspace::find_root(manifest_path, &manifest_toml, rev)?
+        };
+        let workspace_root = workspace_root
+            .as_ref()
+            .map(|(path, toml)| (path.as_path(), toml));
+
+        // `cargo vendor` puts the packages next to the workspace root by default. Like the registry,
+        // the vendor directory is always read from disk, as it is rarely committed.
+        let root_dir = workspace_root
+            .map_or(manifest_path, |(path, _)| path)
+            .parent()
+            .unwrap_or_else(|| Path::new("."));
+        dg.vendor_dir = Some(root_dir.join("vendor")).filter(|dir| dir.is_dir());
+        workspace::inherit(manifest_path, &mut manifest_toml, workspace_root)?;
+
+        // Get the name and version of the root project and of any workspace members. A manifest
+        // with both a [package] and a [workspace] table contributes its own package as well.
+        let mut root_crates_tomls = vec![];
+        if manifest_toml.get("package").is_some() {
+            root_crates_tomls.push(parse_root_crate(manifest_path, manifest_toml)?);
+        } else if manifest_toml.get("workspace").is_none() {
+            return Err(Error::InvalidFile {
+                path: manifest_path.to_path_buf(),
+                package: None,
+                key: None,
+                message: "no [package] or [workspace] table found".into(),
+            });
+        }
 
-        // Get the name and version of the root project.
-        let root_crates_tomls = {
-            if let Some(table) = manifest_toml.get("package") {
-                if let Some(table) = table.as_table() {
-                    if let (Some(&Value::String(ref name)), Some(&Value::String(ref ver))) =
-                        (table.get("name"), table.get("version"))
-                    {
-                        let (name, ver) = (name.to_string(), ver.to_string());
-                        vec![(RootCrate { name, ver }, manifest_toml)]
-                    } else {
-                        return Err(Error::Toml(
-                            "No 'name' or 'version' fields in [package] table".into(),
-                        ));
-                    }
-                } else {
-                    return Err(Error::Toml("Could not parse [package] as a table".into()));
-                }
-            } else {
-                // TODO: Check for workspace here.
-                return Err(Error::Toml("No [package] table found".into()));
+        for member_path in member_manifests.iter() {
+            if member_path == manifest_path {
+                continue;
             }
-        };
 
-        let mut root_deps_map = HashMap::new();
+            let mut member_toml = util::toml_from_file_at(member_path, rev)?;
+            workspace::inherit(member_path, &mut member_toml, workspace_root)?;
+            if member_toml.get("package").is_none() {
+                return Err(Error::invalid_key(
+                    member_path,
+                    "package",
+                    "workspace members must have a [package] table",
+                ));
+            }
+            root_crates_tomls.push(parse_root_crate(member_path, member_toml)?);
+        }
+
+        if root_crates_tomls.is_empty() {
+            return Err(Error::invalid_key(
+                manifest_path,
+                "workspace.members",
+                "no members found",
+            ));
+        }
+
+        // Features given as `crate/feature` only apply to that root crate, while `dep/feature`
+        // selects a feature of a dependency. Every selected feature must be defined by at least one
+        // root crate, or name one of its dependencies.
+        let mut unused_features = self
+            .cfg
+            .features
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<HashSet<_>>();
 
-        for (root_crate, manifest_toml) in root_crates_tomls.iter() {
+        let root_names = root_crates_tomls
+            .iter()
+            .map(|(root_crate, _, _)| root_crate.name.as_str())
+            .collect::<HashSet<_>>();
+
+        for (root_crate, manifest_path, manifest_toml) in root_crates_tomls.iter() {
             let root_name = &root_crate.name;
             let mut dep_kinds_map = HashMap::new();
+            let mut dep_platforms_map = HashMap::new();
+            let mut dep_features_map = HashMap::new();
 
-            if let Some(table) = manifest_toml.get("dependencies") {
-                if let Some(table) = table.as_table() {
-                    for (mut dep_name, dep_table) in table.iter() {
-                        if let Some(&Value::String(ref name)) = dep_table.get("package") {
-                            dep_name = name;
-                        }
+            let selected_features = self
+                .cfg
+                .features
+                .iter()
+                .flatten()
+                .filter_map(|feature| match feature.find('/') {
+                    Some(i) if &feature[..i] == root_name => Some((feature, &feature[i + 1..])),
+                    Some(i) if root_names.contains(&feature[..i]) => None,
+                    _ => Some((feature, feature.as_str())),
+                })
+                .collect::<Vec<_>>();
+            let (enabled_deps, unknown_features) = features::enabled_optional_deps(
+                manifest_path,
+                manifest_toml,
+                &selected_features
+                    .iter()
+                    .map(|&(_, feature)| feature)
+                    .collect::<Vec<_>>(),
+                self.cfg.all_features,
+                self.cfg.no_default_features,
+            )?;
+            for (given, feature) in selected_features {
+                if !unknown_features.contains(&feature) {
+                    unused_features.remove(given.as_str());
+                }
+            }
+
+            self.add_root_deps(
+                &mut dep_kinds_map,
+                &mut dep_features_map,
+                manifest_toml,
+                &enabled_deps,
+            );
 
-                        if let Some(&Value::Boolean(true)) = dep_table.get("optional") {
-                            if self.cfg.optional_deps {
-                                add_kind(
-                                    &mut dep_kinds_map,
-                                    dep_name.to_string(),
-                                    DepKind::Optional,
-                                );
-                            }
-                        } else if self.cfg.regular_deps {
-                            add_kind(&mut dep_kinds_map, dep_name.to_string(), DepKind::Regular);
+            // Add platform-specific dependencies, declared in [target.'cfg(..)'.dependencies] or
+            // [target.<triple>.dependencies] tables. Deps which are also declared unconditionally
+            // are not considered platform-specific.
+            if let Some(Value::Table(targets)) = manifest_toml.get("target") {
+                let mut target_kinds_map = HashMap::new();
+
+                for (platform, target_toml) in targets.iter() {
+                    // With --target, skip tables for other platforms.
+                    if !self.platform_matches(platform)? {
+                        continue;
+                    }
+
+                    let mut platform_kinds_map = HashMap::new();
+                    self.add_root_deps(
+                        &mut platform_kinds_map,
+                        &mut dep_features_map,
+                        target_toml,
+                        &enabled_deps,
+                    );
+
+                    for (dep_name, kinds) in platform_kinds_map {
+                        if !dep_kinds_map.contains_key(&dep_name) {
+                            add_platform(&mut dep_platforms_map, dep_name.clone(), platform);
+                        }
+                        for kind in kinds {
+                            add_kind(&mut target_kinds_map, dep_name.clone(), kind);
                         }
                     }
                 }
+
+                for (dep_name, kinds) in target_kinds_map {
+                    for kind in kinds {
+                        add_kind(&mut dep_kinds_map, dep_name.clone(), kind);
+                    }
+                }
             }
 
-            if self.cfg.build_deps {
-                if let Some(table) = manifest_toml.get("build-dependencies") {
-                    if let Some(table) = table.as_table() {
-                        for (mut dep_name, dep_table) in table.iter() {
-                            if let Some(&Value::String(ref name)) = dep_table.get("package") {
-                                dep_name = name;
-                            }
+            dg.root_deps_map
+                .insert(root_name.to_string(), dep_kinds_map);
+            dg.root_platforms_map
+                .insert(root_name.to_string(), dep_platforms_map);
+            dg.root_features_map
+                .insert(root_name.to_string(), dep_features_map);
+            dg.root_reqs_map
+                .insert(root_name.to_string(), unify::declared_reqs(manifest_toml));
+        }
 
-                            add_kind(&mut dep_kinds_map, dep_name.to_string(), DepKind::Build);
-                        }
-                    }
+        if let Some(feature) = unused_features.iter().min() {
+            return Err(Error::InvalidConfig(format!(
+                "Feature '{}' is not defined by any root crate",
+                feature
+            )));
+        }
+
+        dg.root_crates = root_crates_tomls
+            .into_iter()
+            .map(|(root_crate, _, _)| root_crate)
+            .collect();
+        Ok(())
+    }
+
+    // Checks whether a [target] table applies to the selected platform. All tables apply if no
+    // --target was given.
+    fn platform_matches(&self, platform: &str) -> Result<bool> {
+        match self.platform {
+            Some(ref target) => target.matches(platform),
+            None => Ok(true),
+        }
+    }
+
+    // Finds the dependencies of a registry package which are only declared for other platforms
+    // than the one selected with --target. Such edges in the lock file are never compiled. If the
+    // package's manifest cannot be found locally, none of its edges are pruned.
+    fn inactive_platform_deps(&self, pkg: &LockPackage) -> Result<HashSet<String>> {
+        let mut inactive_deps = HashSet::new();
+
+        let is_registry = pkg
+            .source
+            .as_ref()
+            .is_some_and(|source| source.starts_with("registry+"));
+        if self.platform.is_none() || !is_registry {
+            return Ok(inactive_deps);
+        }
+        let manifest_toml = match util::find_registry_manifest(
+            self.cfg.cargo_home.as_deref().map(Path::new),
+            &pkg.name,
+            &pkg.ver,
+        )? {
+            Some(manifest_path) => util::toml_from_file(manifest_path)?,
+            None => return Ok(inactive_deps),
+        };
+
+        // Deps declared unconditionally or for a matching platform stay active.
+        let mut active_deps = dep_names(&manifest_toml);
+        if let Some(Value::Table(targets)) = manifest_toml.get("target") {
+            for (platform, target_toml) in targets.iter() {
+                if self.platform_matches(platform)? {
+                    active_deps.extend(dep_names(target_toml));
+                } else {
+                    inactive_deps.extend(dep_names(target_toml));
                 }
             }
+        }
 
-            if self.cfg.dev_deps {
-                if let Some(table) = manifest_toml.get("dev-dependencies") {
-                    if let Some(table) = table.as_table() {
-                        for (mut dep_name, dep_table) in table.iter() {
-                            if let Some(&Value::String(ref name)) = dep_table.get("package") {
-                                dep_name = name;
-                            }
+        Ok(&inactive_deps - &active_deps)
+    }
 
-                            add_kind(&mut dep_kinds_map, dep_name.to_string(), DepKind::Dev);
+    // Adds the deps from the dependency tables of `deps_toml`, which is either a manifest or one of
+    // its [target] tables. Optional deps are added if they are enabled by a selected feature, in
+    // which case the features are recorded, or if all optional deps were requested.
+    fn add_root_deps(
+        &self,
+        dep_kinds_map: &mut DepKindsMap,
+        dep_features_map: &mut DepFeaturesMap,
+        deps_toml: &Value,
+        enabled_deps: &EnabledDepsMap,
+    ) {
+        if let Some(table) = deps_toml.get("dependencies") {
+            if let Some(table) = table.as_table() {
+                for (dep_key, dep_table) in table.iter() {
+                    let dep_name = match dep_table.get("package") {
+                        Some(Value::String(name)) => name,
+                        _ => dep_key,
+                    };
+
+                    if let Some(Value::Boolean(true)) = dep_table.get("optional") {
+                        let enabled_by =
+                            enabled_deps.get(dep_key).filter(|_| self.cfg.regular_deps);
+                        if let Some(features) = enabled_by {
+                            dep_features_map.insert(dep_name.to_string(), features.clone());
                         }
+
+                        if self.cfg.optional_deps || enabled_by.is_some() {
+                            add_kind(dep_kinds_map, dep_name.to_string(), DepKind::Optional);
+                        }
+                    } else if self.cfg.regular_deps {
+                        add_kind(dep_kinds_map, dep_name.to_string(), DepKind::Regular);
                     }
                 }
             }
+        }
 
-            root_deps_map.insert(root_name.to_string(), dep_kinds_map);
+        if self.cfg.build_deps {
+            if let Some(table) = deps_toml.get("build-dependencies") {
+                if let Some(table) = table.as_table() {
+                    for (mut dep_name, dep_table) in table.iter() {
+                        if let Some(Value::String(name)) = dep_table.get("package") {
+                            dep_name = name;
+                        }
+
+                        add_kind(dep_kinds_map, dep_name.to_string(), DepKind::Build);
+                    }
+                }
+            }
         }
 
-        Ok((
-            root_crates_tomls
-                .iter()
-                .map(|(root_crate, _)| root_crate.clone())
-                .collect(),
-            root_deps_map,
-        ))
+        if self.cfg.dev_deps {
+            if let Some(table) = deps_toml.get("dev-dependencies") {
+                if let Some(table) = table.as_table() {
+                    for (mut dep_name, dep_table) in table.iter() {
+                        if let Some(Value::String(name)) = dep_table.get("package") {
+                            dep_name = name;
+                        }
+
+                        add_kind(dep_kinds_map, dep_name.to_string(), DepKind::Dev);
+                    }
+                }
+            }
+        }
     }
 
     /// Builds a graph of the resolved dependencies declared in the lock file.
-    fn parse_lock_file(
-        &self,
-        lock_path: PathBuf,
-        root_crates: &[RootCrate],
-        root_deps_map: RootDepsMap,
-    ) -> Result<DepGraph> {
-        let lock_toml = util::toml_from_file(lock_path)?;
-
-        let mut dg = DepGraph::new(self.cfg.clone());
-        dg.root_deps_map = root_deps_map;
-
-        if let Some(&Value::Array(ref packages)) = lock_toml.get("package") {
-            for pkg in packages {
-                parse_package(&mut dg, pkg, root_crates)?;
+    fn parse_lock_file(&self, lock_path: PathBuf, dg: &mut DepGraph) -> Result<()> {
+        let lockfile = match self.revision() {
+            Some(rev) => {
+                let lock_toml = util::toml_from_file_at(&lock_path, Some(rev))?;
+                Lockfile::from_toml(&lock_toml, &rev.name(&lock_path))?
             }
-        } else if let Some(root) = lock_toml.get("root") {
-            println!(
-                "Warning: deprecated [root] table found in lock file. Using [root] as [package]."
-            );
+            None => Lockfile::from_file(&lock_path)?,
+        };
+        dg.lockfile_version = Some(lockfile.version);
 
-            parse_package(&mut dg, root, root_crates)?;
-        } else {
-            return Err(Error::Toml("Missing [package] table in lock file".into()));
+        for pkg in lockfile.packages.iter() {
+            let inactive_deps = self.inactive_platform_deps(pkg)?;
+            parse_package(dg, &lockfile, pkg, &inactive_deps)?;
         }
 
-        // Check that all root crates were found in the lock files.
-        for &RootCrate { ref name, ref ver } in root_crates.iter() {
-            if dg.find(&name, &ver).is_none() {
-                return Err(Error::Toml(format!(
-                    "Missing 'name': {} and 'version': {} in lock file",
-                    name, ver
-                )));
+        // Check that all root crates were found in the lock files. A local package with the same
+        // name but another version means that the lock file is out of date.
+        for RootCrate { name, ver } in dg.root_crates.iter() {
+            if dg.find(name, ver, None).is_none() {
+                let lockfile_ver = lockfile
+                    .packages
+                    .iter()
+                    .find(|pkg| &pkg.name == name && pkg.source.is_none())
+                    .map(|pkg| pkg.ver.clone());
+                return Err(Error::VersionMismatch {
+                    name: name.to_string(),
+                    manifest_ver: ver.to_string(),
+                    lockfile_ver,
+                });
             }
         }
 
-        Ok(dg)
+        Ok(())
     }
 }
 
+// Gets the name and version from the [package] table of a root manifest.
+fn parse_root_crate(
+    manifest_path: &Path,
+    manifest_toml: Value,
+) -> Result<(RootCrate, PathBuf, Value)> {
+    let table = manifest_toml
+        .get("package")
+        .and_then(Value::as_table)
+        .ok_or_else(|| Error::invalid_key(manifest_path, "package", "expected a table"))?;
+    let field = |key| match table.get(key) {
+        Some(Value::String(value)) => Ok(value.to_string()),
+        Some(_) => Err(Error::invalid_key(
+            manifest_path,
+            &format!("package.{}", key),
+            "expected a string",
+        )),
+        None => Err(Error::invalid_key(
+            manifest_path,
+            &format!("package.{}", key),
+            "missing field",
+        )),
+    };
+
+    let (name, ver) = (field("name")?, field("version")?);
+    Ok((
+        RootCrate { name, ver },
+        manifest_path.to_path_buf(),
+        manifest_toml,
+    ))
+}
+
 fn add_kind(dep_kinds_map: &mut DepKindsMap, key: String, kind: DepKind) {
-    let kinds = dep_kinds_map.entry(key).or_insert_with(|| vec![]);
+    let kinds = dep_kinds_map.entry(key).or_default();
     kinds.push(kind);
 }
 
-fn parse_package(dg: &mut DepGraph, pkg: &Value, root_crates: &[RootCrate]) -> Result<()> {
-    let name = pkg
-        .get("name")
-        .expect("No 'name' field in Cargo.lock [package] table")
-        .as_str()
-        .expect("'name' field of [package] table in Cargo.lock was not a valid string")
-        .to_owned();
-    let ver = pkg
-        .get("version")
-        .expect("No 'version' field in Cargo.lock [package] table")
-        .as_str()
-        .expect("'version' field of [package] table in Cargo.lock was not a valid string")
-        .to_owned();
-
-    // If --filter was specified, keep only packages that were indicated.
-    let filter = dg.cfg.filter.clone();
-    if let Some(ref filter_deps) = filter {
-        // NOTE: This will filter out root crates if they are passed in. This is useful for e.g.
-        // workspaces if the user does not want all roots.
-        if !filter_deps.contains(&name) {
-            return Ok(());
-        }
+fn add_platform(dep_platforms_map: &mut DepPlatformsMap, key: String, platform: &str) {
+    let platforms = dep_platforms_map.entry(key).or_default();
+    if !platforms.iter().any(|p| p == platform) {
+        platforms.push(platform.to_string());
     }
+}
 
-    let id = dg.find_or_add(&name, &ver);
-
-    if dg.root_deps_map.contains_key(&name) {
-        // If this is a root crate, check that this crate is in `root_crates` with the same version.
-        if !root_crates
-            .iter()
-            .any(|root_crate| root_crate.name == name && root_crate.ver == ver)
-        {
-            return Err(Error::Generic(format!(
-                "Version {} of root crate '{}' in Cargo.lock does not match version specified in \
-                 Cargo.toml",
-                ver, name
-            )));
+// Gets the package names of all deps in the dependency tables of `deps_toml`, which is either a
+// manifest or one of its [target] tables.
+fn dep_names(deps_toml: &Value) -> HashSet<String> {
+    let mut names = HashSet::new();
+
+    for table_name in &["dependencies", "build-dependencies", "dev-dependencies"] {
+        if let Some(Value::Table(table)) = deps_toml.get(table_name) {
+            for (dep_name, dep_table) in table.iter() {
+                match dep_table.get("package") {
+                    Some(Value::String(name)) => names.insert(name.to_string()),
+                    _ => names.insert(dep_name.to_string()),
+                };
+            }
         }
     }
 
-    if let Some(&Value::Array(ref deps)) = pkg.get("dependencies") {
-        for dep in deps {
-            let dep_vec = dep.as_str().unwrap_or("").split(' ').collect::<Vec<_>>();
-            let dep_name = dep_vec[0].to_string();
-            let dep_ver = dep_vec[1];
+    names
+}
 
-            if let Some(ref filter_deps) = filter {
-                if !filter_deps.contains(&dep_name) {
-                    continue;
-                }
-            }
+fn parse_package(
+    dg: &mut DepGraph,
+    lockfile: &Lockfile,
+    pkg: &LockPackage,
+    inactive_deps: &HashSet<String>,
+) -> Result<()> {
+    let LockPackage {
+        name, ver, source, ..
+    } = pkg;
+
+    let id = dg.find_or_add(name, ver, source.as_deref());
+    let is_root = dg.is_root(id);
+
+    for &dep in pkg.deps.iter() {
+        let LockPackage {
+            name: dep_name,
+            ver: dep_ver,
+            source: dep_source,
+            ..
+        } = &lockfile.packages[dep];
+
+        if inactive_deps.contains(dep_name) {
+            // This dep is not compiled for the platform selected with --target.
+            continue;
+        }
 
-            if let Some(dep_kinds_map) = dg.root_deps_map.get(&name) {
-                if dep_kinds_map.get(&dep_name).is_none() {
-                    // This dep was filtered out when adding root dependencies.
-                    continue;
-                }
+        let mut dev_only = false;
+        if let Some(dep_kinds_map) = dg.root_deps_map.get(name).filter(|_| is_root) {
+            match dep_kinds_map.get(dep_name) {
+                // This dep was filtered out when adding root dependencies.
+                None => continue,
+                Some(kinds) => dev_only = kinds.iter().all(|kind| *kind == DepKind::Dev),
             }
-
-            dg.add_child(id, &dep_name, dep_ver);
         }
+
+        dg.add_child(id, dep_name, dep_ver, dep_source.as_deref(), dev_only);
     }
 
     Ok(())
@@ -0,0 +1,96 @@
+use crate::{
+    error::{Error, Result},
+    util,
+};
+use regex::Regex;
+use semver::{Version, VersionReq};
+use std::fmt;
+
+/// Selects crates by name and, optionally, version. Every option that takes crate names accepts
+/// selectors:
+///
+/// - `name` matches the crate with that exact name.
+/// - A glob such as `tokio-*` or `windows_[!a]*` matches every crate whose whole name fits, using
+///   `util::glob_match`.
+/// - `re:REGEX` matches every crate with a name that the regular expression matches anywhere,
+///   e.g. `re:^aws-(sdk|smithy)-`.
+///
+/// Any of these can be followed by `@` and a version requirement, which uses Cargo's syntax, e.g.
+/// `serde@1`, `syn@^1.0.60`, `tokio@>=1.20, <2` or `rand@=0.7.3`.
+#[derive(Clone, Debug)]
+pub struct CrateSelector {
+    spec: String,
+    name: NamePattern,
+    req: Option<VersionReq>,
+}
+
+#[derive(Clone, Debug)]
+enum NamePattern {
+    Exact(String),
+    Glob(String),
+    Regex(Regex),
+}
+
+impl CrateSelector {
+    /// Parses a selector, failing with `Error::InvalidConfig` if it is not valid.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = |message: String| {
+            Error::InvalidConfig(format!("Invalid crate '{}': {}", spec, message))
+        };
+
+        // Crate names and version requirements cannot contain '@'.
+        let (name, req) = match spec.find('@') {
+            Some(i) => (
+                &spec[..i],
+                Some(VersionReq::parse(&spec[i + 1..]).map_err(|e| invalid(e.to_string()))?),
+            ),
+            None => (spec, None),
+        };
+
+        let name = if let Some(regex) = name.strip_prefix("re:") {
+            NamePattern::Regex(Regex::new(regex).map_err(|e| invalid(e.to_string()))?)
+        } else if util::is_glob(name) {
+            NamePattern::Glob(name.into())
+        } else if name.is_empty() {
+            return Err(invalid("missing crate name".into()));
+        } else {
+            NamePattern::Exact(name.into())
+        };
+
+        Ok(Self {
+            spec: spec.into(),
+            name,
+            req,
+        })
+    }
+
+    /// Parses a list of selectors.
+    pub fn parse_all<S: AsRef<str>>(specs: &[S]) -> Result<Vec<Self>> {
+        specs
+            .iter()
+            .map(|spec| Self::parse(spec.as_ref()))
+            .collect()
+    }
+
+    /// Checks whether the crate with the given name and version is selected.
+    pub fn matches(&self, name: &str, ver: &str) -> bool {
+        let name_matches = match self.name {
+            NamePattern::Exact(ref exact) => exact == name,
+            NamePattern::Glob(ref glob) => util::glob_match(glob, name),
+            NamePattern::Regex(ref pattern) => pattern.is_match(name),
+        };
+
+        name_matches
+            && self.req.as_ref().is_none_or(|req| {
+                Version::parse(ver)
+                    .ok()
+                    .is_some_and(|ver| req.matches(&ver))
+            })
+    }
+}
+
+impl fmt::Display for CrateSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.spec)
+    }
+}
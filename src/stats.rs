@@ -0,0 +1,268 @@
+use crate::{
+    error::{Error, Result},
+    graph::{DepGraph, Edge, Node},
+};
+use std::{cmp::Reverse, collections::VecDeque, io::Write, str::FromStr};
+
+/// How to write the report of `DepGraph::write_stats`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatsFormat {
+    /// A table aligned for reading in a terminal.
+    Table,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// A JSON array with an object for each crate.
+    Json,
+}
+
+impl FromStr for StatsFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(Self::Table),
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(Error::InvalidConfig(format!(
+                "Invalid format '{}': expected table, csv or json",
+                s
+            ))),
+        }
+    }
+}
+
+/// The statistics of a crate in the graph, as found by `DepGraph::stats`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrateStats {
+    /// The node of the crate.
+    pub node: Node,
+    /// How much the crate weighs on the graph, if a root crate depends on it directly.
+    pub weight: Option<Weight>,
+    /// The number of crates that depend on the crate directly.
+    pub fan_in: usize,
+    /// The number of crates that the crate depends on directly.
+    pub fan_out: usize,
+}
+
+/// How much a direct dependency of the root crates weighs on the graph.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weight {
+    /// The number of crates that the dependency pulls in, directly or not, not counting itself.
+    pub transitive: usize,
+    /// The number of those crates that nothing else pulls in, which would go away along with it,
+    /// i.e. the crates that it dominates.
+    pub exclusive: usize,
+    /// The distance from the dependency to the crate furthest below it, following the shortest
+    /// path to each crate.
+    pub max_depth: usize,
+}
+
+impl DepGraph {
+    /// Computes the statistics of each crate that would be rendered. The direct dependencies of the
+    /// root crates come first, sorted by how many crates are exclusive to them, followed by the
+    /// other crates sorted by fan-in.
+    pub fn stats(&self) -> Vec<CrateStats> {
+        let visible = |n: Node| !self.nodes[n].hidden && self.within_limits(&self.nodes[n]);
+        let children = self.adjacency(false);
+        let roots = (0..self.nodes.len())
+            .map(|n| self.is_root(n) && visible(n))
+            .collect::<Vec<_>>();
+
+        let mut fan_in = vec![0; self.nodes.len()];
+        let mut direct = vec![false; self.nodes.len()];
+        for &Edge(parent, child) in self.edges.iter() {
+            fan_in[child] += 1;
+            direct[child] |= roots[parent] && !roots[child];
+        }
+
+        let tree = self.dominator_tree();
+        let mut stats = (0..self.nodes.len())
+            .filter(|&n| visible(n))
+            .map(|n| CrateStats {
+                node: n,
+                weight: Some(n)
+                    .filter(|&n| direct[n])
+                    .map(|n| self.weight(&children, tree.dominated[n], n)),
+                fan_in: fan_in[n],
+                fan_out: children[n].len(),
+            })
+            .collect::<Vec<_>>();
+
+        stats.sort_by_key(|stats| {
+            let dep = &self.nodes[stats.node];
+            let weight = stats.weight.map(|w| (w.exclusive, w.transitive));
+            (
+                stats.weight.is_none(),
+                Reverse(weight),
+                Reverse(stats.fan_in),
+                &dep.name,
+                &dep.ver,
+            )
+        });
+        stats
+    }
+
+    /// Writes the statistics of each crate, one crate per row.
+    pub fn write_stats<W: Write>(
+        &self,
+        stats: &[CrateStats],
+        format: StatsFormat,
+        w: &mut W,
+    ) -> Result<()> {
+        let rows = stats
+            .iter()
+            .map(|stats| {
+                let dep = &self.nodes[stats.node];
+                let weight = |field: fn(&Weight) -> usize| stats.weight.as_ref().map(field);
+                (
+                    [dep.name.as_str(), dep.ver.as_str()],
+                    [
+                        weight(|w| w.transitive),
+                        weight(|w| w.exclusive),
+                        weight(|w| w.max_depth),
+                        Some(stats.fan_in),
+                        Some(stats.fan_out),
+                    ],
+                )
+            })
+            .collect::<Vec<_>>();
+
+        match format {
+            StatsFormat::Table => {
+                let header = [
+                    "Crate",
+                    "Version",
+                    "Transitive",
+                    "Exclusive",
+                    "Max depth",
+                    "Fan-in",
+                    "Fan-out",
+                ];
+                let cells = rows
+                    .iter()
+                    .map(|(strings, numbers)| {
+                        let numbers = numbers
+                            .iter()
+                            .map(|n| n.map_or_else(|| "-".into(), |n| n.to_string()));
+                        strings
+                            .iter()
+                            .map(|s| s.to_string())
+                            .chain(numbers)
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+                let widths = (0..header.len())
+                    .map(|i| {
+                        cells
+                            .iter()
+                            .map(|row| row[i].len())
+                            .chain(Some(header[i].len()))
+                            .max()
+                            .unwrap_or(0)
+                    })
+                    .collect::<Vec<_>>();
+
+                // Names are aligned to the left and numbers to the right.
+                let header = header.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+                for row in Some(&header).into_iter().chain(cells.iter()) {
+                    let line = row
+                        .iter()
+                        .zip(widths.iter())
+                        .enumerate()
+                        .map(|(i, (cell, &width))| match i {
+                            0 | 1 => format!("{:<1$}", cell, width),
+                            _ => format!("{:>1$}", cell, width),
+                        })
+                        .collect::<Vec<_>>();
+                    writeln!(w, "{}", line.join("  ").trim_end())?;
+                }
+            }
+            StatsFormat::Csv => {
+                writeln!(
+                    w,
+                    "name,version,transitive,exclusive,max_depth,fan_in,fan_out"
+                )?;
+                for (strings, numbers) in rows.iter() {
+                    let numbers = numbers
+                        .iter()
+                        .map(|n| n.map_or_else(String::new, |n| n.to_string()));
+                    let row = strings
+                        .iter()
+                        .map(|s| csv_field(s))
+                        .chain(numbers)
+                        .collect::<Vec<_>>();
+                    writeln!(w, "{}", row.join(","))?;
+                }
+            }
+            StatsFormat::Json => {
+                let keys = ["transitive", "exclusive", "max_depth", "fan_in", "fan_out"];
+                writeln!(w, "[")?;
+                for (i, (strings, numbers)) in rows.iter().enumerate() {
+                    let numbers = keys.iter().zip(numbers.iter()).map(|(key, n)| {
+                        let value = n.map_or_else(|| "null".into(), |n| n.to_string());
+                        format!("\"{}\": {}", key, value)
+                    });
+                    let fields = ["name", "version"]
+                        .iter()
+                        .zip(strings.iter())
+                        .map(|(key, s)| format!("\"{}\": {}", key, json_string(s)))
+                        .chain(numbers)
+                        .collect::<Vec<_>>();
+                    let comma = if i + 1 < rows.len() { "," } else { "" };
+                    writeln!(w, "  {{{}}}{}", fields.join(", "), comma)?;
+                }
+                writeln!(w, "]")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Measures a direct dependency of the root crates, given the number of crates it dominates.
+    fn weight(&self, children: &[Vec<Node>], dominated: usize, dep: Node) -> Weight {
+        // Walk down from the dependency, breadth-first so that each crate gets its shortest
+        // distance.
+        let mut depths = vec![None; self.nodes.len()];
+        let mut queue = VecDeque::new();
+        depths[dep] = Some(0);
+        queue.push_back(dep);
+        while let Some(n) = queue.pop_front() {
+            for &child in children[n].iter() {
+                if depths[child].is_none() {
+                    depths[child] = depths[n].map(|depth| depth + 1);
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        Weight {
+            transitive: depths.iter().flatten().count() - 1,
+            exclusive: dominated,
+            max_depth: depths.iter().flatten().copied().max().unwrap_or(0),
+        }
+    }
+}
+
+// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.into()
+    }
+}
+
+// Writes a string as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
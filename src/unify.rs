@@ -0,0 +1,177 @@
+use crate::{
+    dupes::DuplicateVersion,
+    error::Result,
+    graph::{DepGraph, Node},
+    project::DepReqsMap,
+    util,
+};
+use semver::{Version, VersionReq};
+use std::path::Path;
+use toml::Value;
+
+/// What it would take to leave a single version of a duplicated crate, as found by
+/// `DepGraph::unification`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Unification {
+    /// Every requirement allows this version, so `cargo update` would unify on it.
+    CargoUpdate(Node),
+    /// The versions are semver compatible, but these parents require a version that excludes the
+    /// newest one, so they need to be upgraded.
+    ParentUpgrade(Vec<Node>),
+    /// The versions are not semver compatible, e.g. `1.x` and `2.x`.
+    IncompatibleMajors,
+    /// The crate comes from several sources, e.g. crates.io and a git repository.
+    SeveralSources,
+    /// The requirements of these parents could not be found locally.
+    Unknown(Vec<Node>),
+}
+
+/// Reads the version requirements with which a manifest declares its dependencies, including those
+/// in [target] tables, by package name.
+pub fn declared_reqs(manifest_toml: &Value) -> DepReqsMap {
+    let mut reqs = DepReqsMap::new();
+
+    add_declared_reqs(&mut reqs, manifest_toml);
+    if let Some(Value::Table(targets)) = manifest_toml.get("target") {
+        for target_toml in targets.values() {
+            add_declared_reqs(&mut reqs, target_toml);
+        }
+    }
+
+    reqs
+}
+
+// Adds the requirements from the dependency tables of `deps_toml`, which is either a manifest or
+// one of its [target] tables. Dependencies without a version, e.g. git dependencies, are skipped.
+fn add_declared_reqs(reqs: &mut DepReqsMap, deps_toml: &Value) {
+    for table_name in &["dependencies", "build-dependencies", "dev-dependencies"] {
+        if let Some(Value::Table(table)) = deps_toml.get(table_name) {
+            for (dep_key, dep_toml) in table.iter() {
+                let name = match dep_toml.get("package") {
+                    Some(Value::String(name)) => name,
+                    _ => dep_key,
+                };
+                let req = match dep_toml {
+                    Value::String(req) => req,
+                    _ => match dep_toml.get("version") {
+                        Some(Value::String(req)) => req,
+                        _ => continue,
+                    },
+                };
+                reqs.entry(name.to_string())
+                    .or_default()
+                    .push(req.to_string());
+            }
+        }
+    }
+}
+
+impl DepGraph {
+    /// Finds the version requirement with which `parent` declares its dependency on `child`. The
+    /// requirements of root crates come from their manifests, and those of other crates from their
+    /// manifests in the vendor directory or in `registry/src` of the Cargo home. Returns `None` if the
+    /// manifest cannot be found locally.
+    pub fn declared_req(&self, parent: Node, child: Node) -> Result<Option<String>> {
+        let is_root = self.is_root(parent);
+        let (parent, child) = (&self.nodes[parent], &self.nodes[child]);
+
+        let reqs = if is_root {
+            self.root_reqs_map
+                .get(&parent.name)
+                .and_then(|reqs| reqs.get(&child.name))
+                .cloned()
+        } else {
+            let vendored = match self.vendor_dir {
+                Some(ref vendor_dir) => {
+                    util::find_vendored_manifest(vendor_dir, &parent.name, &parent.ver)?
+                }
+                None => None,
+            };
+            let manifest_path = match vendored {
+                Some(manifest_path) => Some(manifest_path),
+                None if parent.source.is_some() => util::find_registry_manifest(
+                    self.cfg.cargo_home.as_deref().map(Path::new),
+                    &parent.name,
+                    &parent.ver,
+                )?,
+                None => None,
+            };
+            match manifest_path {
+                Some(manifest_path) => {
+                    declared_reqs(&util::toml_from_file(manifest_path)?).remove(&child.name)
+                }
+                None => None,
+            }
+        };
+
+        // A crate can depend on several versions of another under different names, so pick the
+        // requirement that the child's version meets.
+        let reqs = reqs.unwrap_or_default();
+        let child_ver = Version::parse(&child.ver).ok();
+        let matching = reqs
+            .iter()
+            .find(|req| match (VersionReq::parse(req), child_ver.as_ref()) {
+                (Ok(req), Some(ver)) => req.matches(ver),
+                _ => false,
+            });
+        Ok(matching.or_else(|| reqs.first()).cloned())
+    }
+
+    /// Works out how the versions of a duplicated crate, sorted by version, could be unified from
+    /// the requirements of their parents.
+    pub fn unification(&self, versions: &[DuplicateVersion]) -> Unification {
+        let source = &self.nodes[versions[0].node].source;
+        if versions
+            .iter()
+            .any(|version| self.nodes[version.node].source != *source)
+        {
+            return Unification::SeveralSources;
+        }
+
+        let mut reqs = vec![];
+        let mut unknown = vec![];
+        for version in versions.iter() {
+            for (&parent, req) in version.parents.iter().zip(version.requirements.iter()) {
+                match req.as_ref().and_then(|req| VersionReq::parse(req).ok()) {
+                    Some(req) => reqs.push((parent, req)),
+                    None => unknown.push(parent),
+                }
+            }
+        }
+        let vers = versions
+            .iter()
+            .map(|version| Version::parse(&self.nodes[version.node].ver).ok())
+            .collect::<Option<Vec<_>>>();
+        let vers = match vers {
+            Some(vers) => vers,
+            None => return Unification::Unknown(unknown),
+        };
+
+        // Cargo picks the newest version that every requirement allows.
+        if unknown.is_empty() {
+            let unified = versions
+                .iter()
+                .zip(vers.iter())
+                .rev()
+                .find(|(_, ver)| reqs.iter().all(|(_, req)| req.matches(ver)));
+            if let Some((version, _)) = unified {
+                return Unification::CargoUpdate(version.node);
+            }
+        }
+
+        let newest = &vers[vers.len() - 1];
+        if vers.iter().any(|ver| !util::is_compatible(ver, newest)) {
+            Unification::IncompatibleMajors
+        } else if !unknown.is_empty() {
+            Unification::Unknown(unknown)
+        } else {
+            let mut outdated = reqs
+                .into_iter()
+                .filter(|(_, req)| !req.matches(newest))
+                .map(|(parent, _)| parent)
+                .collect::<Vec<_>>();
+            outdated.dedup();
+            Unification::ParentUpgrade(outdated)
+        }
+    }
+}
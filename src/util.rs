@@ -1,9 +1,13 @@
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    git::Revision,
+};
+use semver::Version;
 use std::{
     env,
     fs::{self, File},
-    io::Read,
-    path::{Path, PathBuf},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
 };
 use toml::{self, Value};
 
@@ -13,33 +17,76 @@ pub fn toml_from_file<P: AsRef<Path>>(p: P) -> Result<Value> {
     let mut s = String::new();
     f.read_to_string(&mut s)?;
 
-    let toml: Value = toml::from_str(&s)?;
-    Ok(toml)
+    toml_from_str(&s, p)
+}
+
+/// Parses TOML that was read from somewhere else than a file. The path is only used in errors.
+pub fn toml_from_str<P: AsRef<Path>>(s: &str, p: P) -> Result<Value> {
+    toml::from_str(s).map_err(|err| Error::TomlParse {
+        path: p.as_ref().to_path_buf(),
+        line_col: err.line_col().map(|(line, col)| (line + 1, col + 1)),
+        source: err,
+    })
+}
+
+/// Reads a TOML file from the working tree, or as it was at a git revision if one is given. Errors
+/// name the file as `REV:PATH` in that case.
+pub fn toml_from_file_at<P: AsRef<Path>>(p: P, rev: Option<&Revision>) -> Result<Value> {
+    match rev {
+        Some(rev) => toml_from_str(&rev.read_to_string(p.as_ref())?, rev.name(p.as_ref())),
+        None => toml_from_file(p),
+    }
+}
+
+/// Checks whether a file exists in the working tree, or at a git revision if one is given.
+pub fn is_file_at(path: &Path, rev: Option<&Revision>) -> bool {
+    match rev {
+        Some(rev) => rev.is_file(path),
+        None => path.is_file(),
+    }
+}
+
+/// Resolves the `.` and `..` components of a path without looking at the file system, for paths
+/// that may not exist on disk.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
 }
 
 // TODO: replace with `find_root_manifest_for_wd` in the `cargo` crate?
-pub fn find_file_search_parent_dirs(file: &str) -> Result<PathBuf> {
+// Returns `None` if the file is not found. The file is looked for at the git revision if one is
+// given, but the directories to search must exist in the working tree.
+pub fn find_file_search_parent_dirs(file: &str, rev: Option<&Revision>) -> Result<Option<PathBuf>> {
     let pwd = env::current_dir()?;
     let input_manifest_path = pwd.join(file);
     let file_name = input_manifest_path.file_name().unwrap();
     // Canonicalize the directory to get rid of things like `..`.
     let mut current_dir = input_manifest_path.parent().unwrap().to_path_buf();
-    current_dir = current_dir
-        .canonicalize()
-        .map_err(|e| Error::Generic(format!("Could not canonicalize {:?}: {}", current_dir, e)))?;
+    current_dir = match current_dir.canonicalize() {
+        Ok(dir) => dir,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
     let mut first_try = true;
 
     loop {
         let try_manifest = current_dir.join(file_name);
 
-        if let Ok(metadata) = fs::metadata(&try_manifest) {
-            if metadata.is_file() {
-                if !first_try {
-                    eprintln!("Found {:?} in {:?}.", file_name, current_dir.display());
-                }
-
-                return Ok(try_manifest);
+        if is_file_at(&try_manifest, rev) {
+            if !first_try {
+                eprintln!("Found {:?} in {:?}.", file_name, current_dir.display());
             }
+
+            return Ok(Some(try_manifest));
         }
 
         if first_try {
@@ -52,13 +99,167 @@ pub fn find_file_search_parent_dirs(file: &str) -> Result<PathBuf> {
         }
 
         current_dir = match current_dir.parent() {
-            None => {
-                return Err(Error::Generic(format!(
-                    "Could not find {:?} in {:?} or any parent directory",
-                    file, pwd,
-                )));
-            }
-            Some(ref dir) => dir.to_path_buf(),
+            None => return Ok(None),
+            Some(dir) => dir.to_path_buf(),
         };
     }
 }
+
+/// Returns true if the string contains any glob metacharacters.
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Matches `text` against a shell-style glob pattern. Supports `*`, `?` and character classes
+/// such as `[abc]`, `[a-z]` and `[!a-z]`.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    glob_match_chars(&pattern, &text)
+}
+
+fn glob_match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| glob_match_chars(rest, &text[i..])),
+        Some(('?', rest)) => !text.is_empty() && glob_match_chars(rest, &text[1..]),
+        Some(('[', rest)) => {
+            let (c, text_rest) = match text.split_first() {
+                Some(split) => split,
+                None => return false,
+            };
+            let negated = rest.first() == Some(&'!');
+            let class = if negated { &rest[1..] } else { rest };
+            // A `]` directly after the opening bracket is part of the class.
+            let end = match class.iter().skip(1).position(|&c| c == ']') {
+                Some(end) => end + 1,
+                // No closing bracket, so treat `[` as a literal.
+                None => {
+                    return *c == '[' && glob_match_chars(rest, text_rest);
+                }
+            };
+
+            let mut matched = false;
+            let mut i = 0;
+            while i < end {
+                if i + 2 < end && class[i + 1] == '-' {
+                    matched |= class[i] <= *c && *c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == *c;
+                    i += 1;
+                }
+            }
+
+            matched != negated && glob_match_chars(&class[end + 1..], text_rest)
+        }
+        Some((p, rest)) => text.first() == Some(p) && glob_match_chars(rest, &text[1..]),
+    }
+}
+
+/// Checks whether two versions are semver compatible, i.e. whether a caret requirement can match
+/// both: they share their leftmost non-zero part.
+pub fn is_compatible(a: &Version, b: &Version) -> bool {
+    match (a.major, a.minor) {
+        (0, 0) => (b.major, b.minor, b.patch) == (0, 0, a.patch),
+        (0, minor) => (b.major, b.minor) == (0, minor),
+        (major, _) => b.major == major,
+    }
+}
+
+/// Expands a `/`-separated glob pattern relative to `base`, returning all matching paths that
+/// exist on disk, or at the git revision if one is given, in sorted order. Components without
+/// metacharacters are joined as-is.
+pub fn expand_glob(base: &Path, pattern: &str, rev: Option<&Revision>) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![base.to_path_buf()];
+
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        if !is_glob(component) {
+            for path in paths.iter_mut() {
+                path.push(component);
+            }
+            continue;
+        }
+
+        let mut matches = vec![];
+        for path in paths.iter() {
+            let entries = match rev {
+                Some(rev) => rev.read_dir(path),
+                None if path.is_dir() => fs::read_dir(path)?
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<io::Result<_>>()?,
+                None => continue,
+            };
+            for entry in entries {
+                // Like shells, do not let wildcards match hidden entries.
+                let is_match = match entry.file_name().and_then(|name| name.to_str()) {
+                    Some(name) if !name.starts_with('.') => glob_match(component, name),
+                    _ => false,
+                };
+
+                if is_match {
+                    matches.push(entry);
+                }
+            }
+        }
+        paths = matches;
+    }
+
+    paths.retain(|path| match rev {
+        Some(rev) => rev.is_file(path) || rev.is_dir(path),
+        None => path.exists(),
+    });
+    paths.sort();
+    Ok(paths)
+}
+
+/// Returns the Cargo home directory, `$CARGO_HOME` or `~/.cargo`.
+pub fn cargo_home() -> Option<PathBuf> {
+    env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cargo")))
+}
+
+/// Finds the manifest of a registry package that Cargo has extracted into `registry/src` of the
+/// given Cargo home, or of the default one. Returns `None` if the package has not been downloaded.
+pub fn find_registry_manifest(
+    cargo_home: Option<&Path>,
+    name: &str,
+    ver: &str,
+) -> Result<Option<PathBuf>> {
+    let registry_src = match cargo_home.map(Path::to_path_buf).or_else(self::cargo_home) {
+        Some(cargo_home) => cargo_home.join("registry").join("src"),
+        None => return Ok(None),
+    };
+    if !registry_src.is_dir() {
+        return Ok(None);
+    }
+
+    let pattern = format!("*/{}-{}/Cargo.toml", name, ver);
+    Ok(expand_glob(&registry_src, &pattern, None)?
+        .into_iter()
+        .next())
+}
+
+/// Finds the manifest of a package in a directory made by `cargo vendor`, which puts each package
+/// in a directory named after it, or after it and its version if several versions are vendored.
+/// Returns `None` if the package is not vendored.
+pub fn find_vendored_manifest(vendor_dir: &Path, name: &str, ver: &str) -> Result<Option<PathBuf>> {
+    let versioned = vendor_dir
+        .join(format!("{}-{}", name, ver))
+        .join("Cargo.toml");
+    if versioned.is_file() {
+        return Ok(Some(versioned));
+    }
+
+    let unversioned = vendor_dir.join(name).join("Cargo.toml");
+    if !unversioned.is_file() {
+        return Ok(None);
+    }
+    let manifest_toml = toml_from_file(&unversioned)?;
+    let vendored_ver = manifest_toml
+        .get("package")
+        .and_then(|package| package.get("version"))
+        .and_then(Value::as_str);
+    Ok(Some(unversioned).filter(|_| vendored_ver == Some(ver)))
+}
@@ -0,0 +1,271 @@
+use crate::{
+    error::{Error, Result},
+    git::Revision,
+    util,
+};
+use std::path::{Path, PathBuf};
+use toml::{value::Table, Value};
+
+/// Returns the manifest paths of all members of the workspace declared in `manifest_toml`, in
+/// sorted order. Returns an empty list if the manifest has no `[workspace]` table. The members are
+/// looked for at the git revision if one is given.
+pub fn member_manifests(
+    manifest_path: &Path,
+    manifest_toml: &Value,
+    rev: Option<&Revision>,
+) -> Result<Vec<PathBuf>> {
+    let workspace = match manifest_toml.get("workspace") {
+        Some(Value::Table(table)) => table,
+        Some(_) => {
+            return Err(Error::invalid_key(
+                manifest_path,
+                "workspace",
+                "expected a table",
+            ))
+        }
+        None => return Ok(vec![]),
+    };
+    let root_dir = manifest_path.parent().unwrap();
+    // Paths at a git revision may not exist on disk, so only their `..` components are resolved.
+    let canonicalize = |path: PathBuf| match rev {
+        Some(_) => util::normalize_path(&path),
+        None => path.canonicalize().unwrap_or(path),
+    };
+
+    // Excluded paths also exclude everything below them.
+    let mut excluded = vec![];
+    for pattern in string_array(manifest_path, workspace, "exclude")? {
+        if util::is_glob(pattern) {
+            excluded.extend(util::expand_glob(root_dir, pattern, rev)?);
+        } else {
+            excluded.push(root_dir.join(pattern));
+        }
+    }
+    let excluded = excluded.into_iter().map(canonicalize).collect::<Vec<_>>();
+
+    let mut manifests = vec![];
+    for pattern in string_array(manifest_path, workspace, "members")? {
+        let is_glob = util::is_glob(pattern);
+        let dirs = if is_glob {
+            util::expand_glob(root_dir, pattern, rev)?
+        } else {
+            vec![root_dir.join(pattern)]
+        };
+
+        for dir in dirs {
+            let dir = canonicalize(dir);
+            if excluded.iter().any(|excluded| dir.starts_with(excluded)) {
+                continue;
+            }
+
+            let manifest = dir.join("Cargo.toml");
+            if !util::is_file_at(&manifest, rev) {
+                // Globs may match unrelated directories, but explicit members must exist.
+                if is_glob {
+                    continue;
+                }
+                return Err(Error::invalid_key(
+                    manifest_path,
+                    "workspace.members",
+                    &format!("member {:?} does not contain a Cargo.toml", dir),
+                ));
+            }
+
+            if !manifests.contains(&manifest) {
+                manifests.push(manifest);
+            }
+        }
+    }
+
+    manifests.sort();
+    Ok(manifests)
+}
+
+/// Finds the root manifest of the workspace that the package at `manifest_path` belongs to, either
+/// through an explicit `package.workspace` key or by searching parent directories, at the git
+/// revision if one is given. Returns `None` if the package is not a member of any workspace.
+pub fn find_root(
+    manifest_path: &Path,
+    manifest_toml: &Value,
+    rev: Option<&Revision>,
+) -> Result<Option<(PathBuf, Value)>> {
+    let manifest_dir = manifest_path.parent().unwrap();
+
+    if let Some(root_dir) = manifest_toml
+        .get("package")
+        .and_then(|package| package.get("workspace"))
+    {
+        let root_dir = root_dir.as_str().ok_or_else(|| {
+            Error::invalid_key(
+                manifest_path,
+                "package.workspace",
+                "expected a path to the workspace root",
+            )
+        })?;
+        let root_path = manifest_dir.join(root_dir).join("Cargo.toml");
+        let root_toml = util::toml_from_file_at(&root_path, rev)?;
+        return Ok(Some((root_path, root_toml)));
+    }
+
+    for dir in manifest_dir.ancestors().skip(1) {
+        let root_path = dir.join("Cargo.toml");
+        if !util::is_file_at(&root_path, rev) {
+            continue;
+        }
+
+        let root_toml = util::toml_from_file_at(&root_path, rev)?;
+        if root_toml.get("workspace").is_none() {
+            continue;
+        }
+
+        // Like Cargo, only the closest workspace is considered.
+        let members = member_manifests(&root_path, &root_toml, rev)?;
+        if members.iter().any(|member| member == manifest_path) {
+            return Ok(Some((root_path, root_toml)));
+        }
+        return Ok(None);
+    }
+
+    Ok(None)
+}
+
+/// Resolves `workspace = true` package fields and dependencies in a member manifest using the
+/// `[workspace.package]` and `[workspace.dependencies]` tables of the workspace root manifest,
+/// given with its path.
+pub fn inherit(
+    manifest_path: &Path,
+    manifest_toml: &mut Value,
+    workspace_root: Option<(&Path, &Value)>,
+) -> Result<()> {
+    let workspace = workspace_root.and_then(|(_, toml)| toml.get("workspace"));
+    let workspace_package = workspace.and_then(|workspace| workspace.get("package"));
+    let workspace_deps = workspace
+        .and_then(|workspace| workspace.get("dependencies"))
+        .and_then(Value::as_table);
+
+    if let Some(Value::Table(package)) = manifest_toml.get_mut("package") {
+        for (key, value) in package.iter_mut() {
+            if !is_inherited(value) {
+                continue;
+            }
+
+            *value = workspace_package
+                .and_then(|package| package.get(key))
+                .cloned()
+                .ok_or_else(|| {
+                    Error::invalid_key(
+                        manifest_path,
+                        &format!("package.{}", key),
+                        "inherited from the workspace, but not found in [workspace.package]",
+                    )
+                })?;
+        }
+    }
+
+    // Collect the dependency tables, including platform-specific ones.
+    let mut dep_tables = vec![];
+    for (key, value) in manifest_toml.as_table_mut().unwrap().iter_mut() {
+        match (key.as_str(), value) {
+            ("target", Value::Table(targets)) => {
+                for (_, target) in targets.iter_mut() {
+                    if let Value::Table(target) = target {
+                        for (key, value) in target.iter_mut() {
+                            match value {
+                                Value::Table(table) if is_dep_table(key) => dep_tables.push(table),
+                                _ => (),
+                            }
+                        }
+                    }
+                }
+            }
+            (key, Value::Table(table)) if is_dep_table(key) => dep_tables.push(table),
+            _ => (),
+        }
+    }
+
+    for table in dep_tables {
+        for (dep_name, dep) in table.iter_mut() {
+            if !is_inherited(dep) {
+                continue;
+            }
+
+            let workspace_dep = workspace_deps
+                .and_then(|deps| deps.get(dep_name))
+                .ok_or_else(|| {
+                    Error::invalid_key(
+                        manifest_path,
+                        dep_name,
+                        "inherited from the workspace, but not found in [workspace.dependencies]",
+                    )
+                })?;
+            let merged = merge_dep(workspace_dep, dep).ok_or_else(|| {
+                Error::invalid_key(
+                    workspace_root.unwrap().0,
+                    &format!("workspace.dependencies.{}", dep_name),
+                    "expected a string or a table",
+                )
+            })?;
+            *dep = Value::Table(merged);
+        }
+    }
+
+    Ok(())
+}
+
+// Merges a member's `dep = { workspace = true, ... }` entry into the workspace's declaration of
+// the dependency. Features are additive; other keys set by the member, such as `optional`, win.
+// Returns `None` if the workspace's declaration is neither a string nor a table.
+fn merge_dep(workspace_dep: &Value, member_dep: &Value) -> Option<Table> {
+    let mut merged = match workspace_dep {
+        Value::String(ver) => {
+            let mut table = Table::new();
+            table.insert("version".into(), Value::String(ver.clone()));
+            table
+        }
+        Value::Table(table) => table.clone(),
+        _ => return None,
+    };
+
+    for (key, value) in member_dep.as_table().unwrap().iter() {
+        match (key.as_str(), merged.get_mut(key)) {
+            ("workspace", _) => (),
+            ("features", Some(Value::Array(features))) => {
+                for feature in value.as_array().into_iter().flatten() {
+                    if !features.contains(feature) {
+                        features.push(feature.clone());
+                    }
+                }
+            }
+            _ => {
+                merged.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    Some(merged)
+}
+
+fn is_dep_table(key: &str) -> bool {
+    key == "dependencies" || key == "build-dependencies" || key == "dev-dependencies"
+}
+
+// Checks for `key.workspace = true`.
+fn is_inherited(value: &Value) -> bool {
+    value.get("workspace") == Some(&Value::Boolean(true))
+}
+
+fn string_array<'a>(manifest_path: &Path, table: &'a Table, key: &str) -> Result<Vec<&'a str>> {
+    let array = match table.get(key) {
+        None => return Ok(vec![]),
+        Some(Value::Array(array)) => array.iter().map(Value::as_str).collect::<Option<_>>(),
+        Some(_) => None,
+    };
+
+    array.ok_or_else(|| {
+        Error::invalid_key(
+            manifest_path,
+            &format!("workspace.{}", key),
+            "expected an array of strings",
+        )
+    })
+}
@@ -1,6 +1,9 @@
 extern crate cargo_deps;
 
-use cargo_deps::{get_dep_graph, render_dep_graph, Config};
+use cargo_deps::{
+    get_dep_graph, render_dep_graph, Cluster, ClusterBy, Config, DiffFormat, Error, StatsFormat,
+};
+use std::{env, fs, process, process::Command};
 
 // Note: these are really just smoke tests to ensure we can use cargo-deps as a lib
 
@@ -16,18 +19,1019 @@ fn get_dep_graph_self() {
 #[rustfmt::skip]
 #[test]
 fn render_dep_graph_self() {
-    let mut cfg = Config::default();
-    cfg.depth = Some(1);
+    let cfg = Config {
+        depth: Some(1),
+        ..Config::default()
+    };
     let graph = get_dep_graph(cfg).unwrap();
     let out = render_dep_graph(graph).unwrap();
     assert_eq!(
         out,
         // #[rustfmt::skip]
         "digraph dependencies {\n\
-         \tn6 [label=\"cargo-deps\", shape=box];\n\
-         \tn7 [label=\"clap\"];\n\tn8 [label=\"toml\"];\n\n\
-         \tn6 -> n7;\n\
-         \tn6 -> n8;\n\
+         \tn8 [label=\"cargo-deps\", shape=box];\n\
+         \tn9 [label=\"clap\"];\n\tn10 [label=\"regex\"];\n\
+         \tn11 [label=\"semver\"];\n\tn12 [label=\"toml\"];\n\n\
+         \tn8 -> n9;\n\
+         \tn8 -> n10;\n\
+         \tn8 -> n11;\n\
+         \tn8 -> n12;\n\
          }\n"
     );
 }
+
+#[test]
+fn get_dep_graph_virtual_workspace() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/workspace/Cargo.toml".into(),
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    let mut roots = graph.root_deps_map.keys().cloned().collect::<Vec<_>>();
+    roots.sort();
+    assert_eq!(roots, vec!["ws-a", "ws-b", "ws-cli"]);
+    assert!(graph.nodes.iter().any(|d| d.name == "itoa"));
+    assert!(!graph.nodes.iter().any(|d| d.name == "excluded"));
+}
+
+#[test]
+fn render_dep_graph_workspace_depth_0() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/workspace/Cargo.toml".into(),
+        depth: Some(0),
+        ..Config::default()
+    };
+    let out = get_dep_graph(cfg).and_then(render_dep_graph).unwrap();
+
+    // Every member is a root at depth 0, even the ones that other members depend on.
+    for member in &["ws-a", "ws-b", "ws-cli"] {
+        assert!(out.contains(&format!("[label=\"{}\", shape=box];", member)));
+    }
+    assert!(!out.contains("[label=\"itoa\"]"));
+
+    let cfg = Config {
+        manifest_path: "tests/fixtures/dev-cycle/Cargo.toml".into(),
+        dev_deps: true,
+        depth: Some(0),
+        ..Config::default()
+    };
+    let out = get_dep_graph(cfg).and_then(render_dep_graph).unwrap();
+    assert!(out.contains("[label=\"a\", shape=box];"));
+    assert!(out.contains("[label=\"b\", shape=box];"));
+}
+
+#[test]
+fn get_dep_graph_mixed_workspace() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/mixed-workspace/Cargo.toml".into(),
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    assert!(graph.root_deps_map.contains_key("mixed-root"));
+    assert!(graph.root_deps_map.contains_key("mixed-member"));
+    assert!(graph.nodes.iter().any(|d| d.name == "itoa"));
+}
+
+#[test]
+fn get_dep_graph_inherited_workspace() {
+    // Run on the member manifest so that the workspace root has to be found in a parent directory.
+    let cfg = Config {
+        manifest_path: "tests/fixtures/inherited-workspace/app/Cargo.toml".into(),
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    assert!(graph
+        .nodes
+        .iter()
+        .any(|d| d.name == "app" && d.ver == "0.3.0"));
+    assert!(graph.nodes.iter().any(|d| d.name == "itoa" && d.is_regular));
+    // Optional deps are not enabled, so `serde_json` is only an orphan node.
+    assert!(!graph
+        .nodes
+        .iter()
+        .any(|d| d.name == "serde_json" && d.is_optional));
+
+    let cfg = Config {
+        manifest_path: "tests/fixtures/inherited-workspace/Cargo.toml".into(),
+        optional_deps: true,
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    assert!(graph
+        .nodes
+        .iter()
+        .any(|d| d.name == "serde_json" && d.is_optional && !d.is_regular));
+}
+
+#[test]
+fn render_dep_graph_target_deps() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/target-deps/Cargo.toml".into(),
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    let out = render_dep_graph(graph).unwrap();
+    assert!(out.contains("n3 -> n1;\n"));
+    assert!(out.contains("n3 -> n4 [style=dotted, label=\"cfg(windows)\"];\n"));
+    assert!(out.contains(
+        "n3 -> n2 [style=dotted, label=\"cfg(target_os = \\\"linux\\\")\\ncfg(unix)\"];\n"
+    ));
+    // Build dependencies are not enabled.
+    assert!(!out.contains("n3 -> n0"));
+}
+
+#[test]
+fn get_dep_graph_target() {
+    // Use the fixture registry for the manifests of transitive dependencies.
+    let cargo_home = Some("tests/fixtures/cargo-home".to_string());
+
+    let cfg = Config {
+        manifest_path: "tests/fixtures/target-deps/Cargo.toml".into(),
+        target: Some("x86_64-unknown-linux-gnu".into()),
+        cargo_home: cargo_home.clone(),
+        build_deps: true,
+        dev_deps: true,
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    let is_reachable = |name: &str| {
+        graph
+            .nodes
+            .iter()
+            .any(|d| d.name == name && d.depth.is_some())
+    };
+    assert!(is_reachable("itoa"));
+    assert!(is_reachable("libc"));
+    assert!(is_reachable("tempfile"));
+    assert!(!is_reachable("winapi"));
+    assert!(!is_reachable("cc"));
+    // Dependencies on atomics, the panic strategy and baseline target features are kept.
+    let node = |name: &str| graph.nodes.iter().position(|d| d.name == name).unwrap();
+    assert!(graph.nodes[node("tempfile")]
+        .children
+        .contains(&node("itoa")));
+
+    let cfg = Config {
+        manifest_path: "tests/fixtures/target-deps/Cargo.toml".into(),
+        target: Some("x86_64-apple-darwin".into()),
+        cargo_home,
+        build_deps: true,
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    assert!(graph.nodes.iter().any(|d| d.name == "cc" && d.is_build));
+    assert!(!graph
+        .nodes
+        .iter()
+        .any(|d| d.name == "winapi" && d.is_regular));
+
+    let cfg = Config {
+        target: Some("not-a-target".into()),
+        ..Config::default()
+    };
+    assert!(get_dep_graph(cfg).is_err());
+}
+
+#[test]
+fn get_dep_graph_features() {
+    let enabled_optional_deps = |cfg: Config| {
+        let graph = get_dep_graph(cfg).unwrap();
+        let mut names = graph
+            .nodes
+            .iter()
+            .filter(|d| d.is_optional)
+            .map(|d| d.name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    let manifest_path = "tests/fixtures/features/Cargo.toml".to_string();
+
+    // The default feature only has a weak dependency feature, which does not enable `itoa`.
+    let cfg = Config {
+        manifest_path: manifest_path.clone(),
+        ..Config::default()
+    };
+    assert!(enabled_optional_deps(cfg).is_empty());
+
+    let cfg = Config {
+        manifest_path: manifest_path.clone(),
+        features: Some(vec!["json".into()]),
+        ..Config::default()
+    };
+    assert_eq!(
+        enabled_optional_deps(cfg),
+        vec!["itoa", "serde", "serde_json"]
+    );
+
+    let cfg = Config {
+        manifest_path: manifest_path.clone(),
+        features: Some(vec!["feat/rand".into()]),
+        no_default_features: true,
+        ..Config::default()
+    };
+    assert_eq!(enabled_optional_deps(cfg), vec!["rand"]);
+
+    // Selecting a feature of a dependency enables it, unless the selection is weak.
+    let cfg = Config {
+        manifest_path: manifest_path.clone(),
+        features: Some(vec!["rand/std".into()]),
+        no_default_features: true,
+        ..Config::default()
+    };
+    assert_eq!(enabled_optional_deps(cfg), vec!["rand"]);
+    let cfg = Config {
+        manifest_path: manifest_path.clone(),
+        features: Some(vec!["rand?/std".into()]),
+        no_default_features: true,
+        ..Config::default()
+    };
+    assert!(enabled_optional_deps(cfg).is_empty());
+    let cfg = Config {
+        manifest_path: manifest_path.clone(),
+        features: Some(vec!["nope/std".into()]),
+        ..Config::default()
+    };
+    assert!(get_dep_graph(cfg).is_err());
+
+    let cfg = Config {
+        manifest_path: manifest_path.clone(),
+        all_features: true,
+        ..Config::default()
+    };
+    assert_eq!(
+        enabled_optional_deps(cfg),
+        vec!["itoa", "rand", "serde", "serde_json"]
+    );
+
+    let cfg = Config {
+        manifest_path,
+        features: Some(vec!["nope".into()]),
+        ..Config::default()
+    };
+    assert!(get_dep_graph(cfg).is_err());
+}
+
+#[test]
+fn get_dep_graph_lockfile_versions() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/lockfile-v1/Cargo.toml".into(),
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    assert_eq!(graph.lockfile_version, Some(1));
+    assert!(graph.nodes.iter().any(|d| d.name == "itoa" && d.is_regular));
+
+    let cfg = Config {
+        manifest_path: "tests/fixtures/lockfile-v4/Cargo.toml".into(),
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    assert_eq!(graph.lockfile_version, Some(4));
+    assert!(graph
+        .nodes
+        .iter()
+        .any(|d| d.name == "rand" && d.ver == "0.7.3"));
+    assert!(graph
+        .nodes
+        .iter()
+        .any(|d| d.name == "rand" && d.ver == "0.8.5"));
+    assert!(graph.nodes.iter().all(|d| d.depth.is_some()));
+}
+
+#[test]
+fn render_dep_graph_duplicates_sorting_last() {
+    // Duplicated crates are labelled with their version even if their name sorts last.
+    let render = |manifest_path: &str| {
+        let cfg = Config {
+            manifest_path: manifest_path.into(),
+            ..Config::default()
+        };
+        get_dep_graph(cfg).and_then(render_dep_graph).unwrap()
+    };
+    let out = render("tests/fixtures/lockfile-v4/Cargo.toml");
+    assert!(out.contains("[label=\"rand v0.7.3\"]"));
+    assert!(out.contains("[label=\"rand v0.8.5\"]"));
+    let out = render("tests/fixtures/duplicates/Cargo.toml");
+    assert!(out.contains("[label=\"serde v1.0.100\"]"));
+    assert!(out.contains("[label=\"serde v1.0.190\"]"));
+}
+
+#[test]
+fn render_dep_graph_root_name_clash() {
+    // A registry crate with the name of a root crate is not a root crate.
+    let cfg = Config {
+        manifest_path: "tests/fixtures/root-name-clash/Cargo.toml".into(),
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    let node = |name: &str, ver: &str| {
+        graph
+            .nodes
+            .iter()
+            .position(|d| d.name == name && d.ver == ver)
+            .unwrap()
+    };
+    assert!(graph.is_root(node("app", "0.1.0")));
+    assert!(!graph.is_root(node("app", "0.0.1")));
+    assert_eq!(graph.nodes[node("cfg-if", "1.0.0")].depth, Some(3));
+
+    let out = render_dep_graph(graph).unwrap();
+    assert!(out.contains("[label=\"app v0.1.0\", shape=box];\n"));
+    assert!(out.contains("[label=\"app v0.0.1\"];\n"));
+}
+
+#[test]
+fn render_dep_graph_sources() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/sources/Cargo.toml".into(),
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    // The crates.io and git versions of `serde` are separate nodes.
+    assert_eq!(graph.nodes.iter().filter(|d| d.name == "serde").count(), 2);
+
+    let out = render_dep_graph(graph).unwrap();
+    assert!(out.contains("[label=\"local\", shape=box, style=rounded];"));
+    assert!(out.contains("[label=\"private\", style=bold];"));
+    assert!(out.contains(
+        "[label=\"serde v1.0.0\\n(git: github.com/someone/serde#0123456)\", style=filled, \
+         fillcolor=lightyellow];"
+    ));
+    assert!(out.contains("[label=\"serde v1.0.0\\n(crates.io)\"];"));
+}
+
+#[test]
+fn get_dep_graph_malformed_input() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/malformed-lockfile/Cargo.toml".into(),
+        ..Config::default()
+    };
+    match get_dep_graph(cfg).err().unwrap() {
+        Error::InvalidFile {
+            path, package, key, ..
+        } => {
+            assert!(path.ends_with("malformed-lockfile/Cargo.lock"));
+            assert_eq!(package, Some((0, Some("log".to_string()))));
+            assert_eq!(key.as_deref(), Some("version"));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+
+    let cfg = Config {
+        manifest_path: "tests/fixtures/malformed-manifest/Cargo.toml".into(),
+        ..Config::default()
+    };
+    match get_dep_graph(cfg).err().unwrap() {
+        Error::TomlParse { path, line_col, .. } => {
+            assert!(path.ends_with("malformed-manifest/Cargo.toml"));
+            assert_eq!(line_col.map(|(line, _)| line), Some(5));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn get_dep_graph_errors() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/cycle/Cargo.toml".into(),
+        ..Config::default()
+    };
+    let err = get_dep_graph(cfg).err().unwrap();
+    assert_eq!(err.exit_code(), 6);
+    match err {
+        Error::CycleDetected { cycles } => assert_eq!(
+            cycles,
+            vec![
+                vec!["cycle-b", "cycle-c", "cycle-b"],
+                vec!["cycle-c", "cycle-d", "cycle-c"],
+            ]
+        ),
+        err => panic!("unexpected error: {}", err),
+    }
+
+    let cfg = Config {
+        manifest_path: "tests/fixtures/missing/Cargo.toml".into(),
+        ..Config::default()
+    };
+    let err = get_dep_graph(cfg).err().unwrap();
+    assert_eq!(err.exit_code(), 3);
+    assert!(matches!(err, Error::ManifestNotFound(_)));
+
+    let cfg = Config {
+        manifest_path: "tests/fixtures/features/Cargo.toml".into(),
+        features: Some(vec!["no-such-feature".into()]),
+        ..Config::default()
+    };
+    let err = get_dep_graph(cfg).err().unwrap();
+    assert_eq!(err.exit_code(), 2);
+    assert!(matches!(err, Error::InvalidConfig(_)));
+}
+
+#[test]
+fn render_dep_graph_allow_cycles() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/cycle/Cargo.toml".into(),
+        allow_cycles: true,
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    assert_eq!(graph.cycles.len(), 2);
+    assert_eq!(graph.describe_cycles().len(), 2);
+    assert!(graph
+        .describe_cycles()
+        .contains(&"cycle-b -> cycle-c -> cycle-b".into()));
+    assert!(graph.nodes.iter().all(|d| d.depth.is_some()));
+
+    let out = render_dep_graph(graph).unwrap();
+    // Nodes: cycle (n0), cycle-b (n1), cycle-c (n2), cycle-d (n3).
+    assert!(out.contains("n0 -> n1;"));
+    assert!(out.contains("n1 -> n2 [color=red, penwidth=2];"));
+    assert!(out.contains("n2 -> n1 [color=red, penwidth=2];"));
+    assert!(out.contains("n2 -> n3 [color=red, penwidth=2];"));
+    assert!(out.contains("n3 -> n2 [color=red, penwidth=2];"));
+}
+
+#[test]
+fn render_dep_graph_dev_cycle() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/dev-cycle/Cargo.toml".into(),
+        dev_deps: true,
+        ..Config::default()
+    };
+    let graph = get_dep_graph(cfg).unwrap();
+    assert!(graph.cycles.is_empty());
+
+    // `a` dev-depends on `b`, which depends on `a`.
+    let out = render_dep_graph(graph).unwrap();
+    assert!(out.contains("n1 -> n0;"));
+    assert!(out.contains("n0 -> n1 [color=blue, style=dashed, constraint=false];"));
+}
+
+#[test]
+fn render_dep_graph_dev_cycle_no_transitive_deps() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/dev-cycle/Cargo.toml".into(),
+        dev_deps: true,
+        transitive_deps: false,
+        ..Config::default()
+    };
+    let out = get_dep_graph(cfg).and_then(render_dep_graph).unwrap();
+
+    // `b` reaches `log` through `a`, but `a` only dev-depends on `b`, so `a -> log` is kept.
+    assert!(out.contains("\tn0 -> n2;\n"));
+    assert!(out.contains("n0 -> n1 [color=blue, style=dashed, constraint=false];"));
+}
+
+#[test]
+fn get_dep_graph_why() {
+    let graph = get_dep_graph(Config::default()).unwrap();
+    let names = |nodes: &[usize]| {
+        nodes
+            .iter()
+            .map(|&n| graph.nodes[n].name.as_str())
+            .collect::<Vec<_>>()
+    };
+
+    let paths = graph.paths_to("unicode-width", 100).unwrap();
+    assert_eq!(paths.count, 2);
+    assert_eq!(
+        names(&paths.paths[0].nodes),
+        ["cargo-deps", "clap", "textwrap", "unicode-width"]
+    );
+    assert_eq!(paths.paths[0].kinds.len(), 3);
+    assert_eq!(
+        names(&paths.paths[1].nodes),
+        ["cargo-deps", "clap", "unicode-width"]
+    );
+
+    // Above the limit, the paths are counted and sampled.
+    let paths = graph.paths_to("unicode-width@0.1.5", 1).unwrap();
+    assert_eq!(paths.count, 2);
+    assert_eq!(paths.paths.len(), 1);
+
+    assert!(graph.paths_to("unicode-width@9.9.9", 1).is_err());
+}
+
+#[test]
+fn get_dep_graph_why_dev_cycle() {
+    let graph = get_dep_graph(Config {
+        manifest_path: "tests/fixtures/dev-cycle/Cargo.toml".into(),
+        dev_deps: true,
+        ..Config::default()
+    })
+    .unwrap();
+    let names = |nodes: &[usize]| {
+        nodes
+            .iter()
+            .map(|&n| graph.nodes[n].name.as_str())
+            .collect::<Vec<_>>()
+    };
+
+    // The path back to `a` through its dev-dependency does not follow `a`'s dev-dependencies again.
+    let paths = graph.paths_to("log", 100).unwrap();
+    let paths = paths
+        .paths
+        .iter()
+        .map(|p| names(&p.nodes))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            vec!["a", "b", "a", "log"],
+            vec!["a", "log"],
+            vec!["b", "a", "log"]
+        ]
+    );
+}
+
+#[test]
+fn render_dep_graph_why() {
+    let mut graph = get_dep_graph(Config::default()).unwrap();
+    graph.retain_paths_to("unicode-width").unwrap();
+
+    let out = render_dep_graph(graph).unwrap();
+    assert!(out.contains("[label=\"textwrap\"]"));
+    assert!(!out.contains("[label=\"toml\"]"));
+    assert!(!out.contains("[label=\"atty\"]"));
+}
+
+#[test]
+fn get_dep_graph_dupes() {
+    let graph = get_dep_graph(Config {
+        manifest_path: "tests/fixtures/duplicates/Cargo.toml".into(),
+        ..Config::default()
+    })
+    .unwrap();
+    let name = |n: usize| format!("{} v{}", graph.nodes[n].name, graph.nodes[n].ver);
+
+    let duplicates = graph.duplicates(3).unwrap();
+    assert_eq!(
+        duplicates
+            .iter()
+            .map(|d| (d.name.as_str(), d.extra_crates))
+            .collect::<Vec<_>>(),
+        [
+            ("rand", 3),
+            ("rand_core", 2),
+            ("getrandom", 1),
+            ("log", 1),
+            ("serde", 1)
+        ]
+    );
+    let rand = &duplicates[0].versions;
+    assert_eq!(name(rand[0].node), "rand v0.7.3");
+    assert_eq!(name(rand[0].parents[0]), "legacy v1.0.0");
+    assert_eq!(name(rand[1].node), "rand v0.8.5");
+    assert_eq!(rand[1].paths.count, 1);
+
+    let mut out = vec![];
+    graph.write_duplicates(&duplicates, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(
+        out.starts_with("5 crates with several versions:\n\nrand (2 versions, 3 extra crates)\n")
+    );
+    assert!(out.contains(
+        "  getrandom v0.1.16\n    required by: rand_core v0.5.1\n    \
+         app v0.1.0 -> legacy v1.0.0 -> rand v0.7.3 -> rand_core v0.5.1 -> getrandom v0.1.16\n"
+    ));
+
+    let graph = get_dep_graph(Config::default()).unwrap();
+    assert!(graph.duplicates(3).unwrap().is_empty());
+}
+
+#[test]
+fn get_dep_graph_dupes_unification() {
+    let hints = |manifest_path: &str| {
+        let graph = get_dep_graph(Config {
+            manifest_path: manifest_path.into(),
+            ..Config::default()
+        })
+        .unwrap();
+        let mut out = vec![];
+        let duplicates = graph.duplicates(0).unwrap();
+        graph.write_duplicates(&duplicates, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    // The requirements of root crates come from their manifests, and those of other crates from
+    // the vendor directory.
+    let out = hints("tests/fixtures/duplicates/Cargo.toml");
+    assert!(out.contains(
+        "rand (2 versions, 3 extra crates)\n  \
+         hint: incompatible major versions, the parents of the older versions need upgrading\n  \
+         rand v0.7.3\n    required by: legacy v1.0.0 (\"0.7\")\n"
+    ));
+    assert!(out.contains(
+        "log (2 versions, 1 extra crate)\n  \
+         hint: every requirement allows v0.4.20, so `cargo update` would unify on it\n"
+    ));
+    assert!(out.contains(
+        "serde (2 versions, 1 extra crate)\n  \
+         hint: semver compatible, but needs an upgrade of legacy v1.0.0, which does not allow \
+         v1.0.190\n  serde v1.0.100\n    required by: legacy v1.0.0 (\"=1.0.100\")\n"
+    ));
+
+    let out = hints("tests/fixtures/sources/Cargo.toml");
+    assert!(out.contains(
+        "  hint: comes from several sources, which only changing the parents or a [patch] would \
+         unify\n"
+    ));
+}
+
+#[test]
+fn get_dep_graph_stats() {
+    let graph = get_dep_graph(Config {
+        manifest_path: "tests/fixtures/duplicates/Cargo.toml".into(),
+        ..Config::default()
+    })
+    .unwrap();
+
+    let stats = graph.stats();
+    let legacy = &stats[0];
+    assert_eq!(graph.nodes[legacy.node].name, "legacy");
+    let weight = legacy.weight.unwrap();
+    assert_eq!(
+        (weight.transitive, weight.exclusive, weight.max_depth),
+        (5, 5, 3)
+    );
+    assert_eq!((legacy.fan_in, legacy.fan_out), (1, 3));
+    // Only the direct dependencies of the root crates are weighed.
+    assert_eq!(stats.iter().filter(|s| s.weight.is_some()).count(), 4);
+
+    let write = |format| {
+        let mut out = vec![];
+        graph.write_stats(&stats, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert!(write(StatsFormat::Table).starts_with(
+        "Crate      Version  Transitive  Exclusive  Max depth  Fan-in  Fan-out\n\
+         legacy     1.0.0             5          5          3       1        3\n"
+    ));
+    let csv = write(StatsFormat::Csv);
+    assert!(csv.contains("\nrand,0.8.5,2,2,2,1,1\n"));
+    assert!(csv.ends_with("\napp,0.1.0,,,,0,4\n"));
+    assert!(write(StatsFormat::Json).contains(
+        "  {\"name\": \"app\", \"version\": \"0.1.0\", \"transitive\": null, \
+         \"exclusive\": null, \"max_depth\": null, \"fan_in\": 0, \"fan_out\": 4}\n]\n"
+    ));
+}
+
+#[test]
+fn get_dep_graph_dominators() {
+    let graph = get_dep_graph(Config::default()).unwrap();
+    let node = |name: &str| graph.nodes.iter().position(|d| d.name == name).unwrap();
+    let tree = graph.dominator_tree();
+
+    // The root crate is only dominated by the virtual root.
+    assert_eq!(tree.idoms[node("cargo-deps")], None);
+    assert_eq!(tree.dominated[node("cargo-deps")], 24);
+    // libc is required by both atty and termion, which atty requires.
+    assert_eq!(tree.idoms[node("libc")], Some(node("atty")));
+    // winapi is required by both ansi_term and atty, which only clap leads to.
+    assert_eq!(tree.idoms[node("winapi")], Some(node("clap")));
+    assert_eq!(tree.dominated[node("clap")], 15);
+    assert!(tree.dominates(node("clap"), node("libc")));
+    assert!(!tree.dominates(node("termion"), node("libc")));
+}
+
+#[test]
+fn render_dep_graph_dominators() {
+    let cfg = Config {
+        dominators: true,
+        ..Config::default()
+    };
+    let out = get_dep_graph(cfg).and_then(render_dep_graph).unwrap();
+    assert!(out.contains("\tn9 [label=\"clap\\ngates 15 crates\"];\n"));
+    assert!(out.contains("\tn12 [label=\"toml\\ngates 1 crate\"];\n"));
+    assert!(out.contains("\tn5 [label=\"libc\"];\n"));
+    // Tree edges between crates that do not depend on each other directly are dotted.
+    assert!(out.contains("\tn9 -> n3 [style=dotted];\n"));
+    assert!(out.contains("\tn4 -> n5;\n"));
+    assert!(!out.contains("\tn6 -> n5"));
+}
+
+#[test]
+fn get_dep_graph_diff() {
+    let graph = |lock_path: Option<&str>| {
+        get_dep_graph(Config {
+            manifest_path: "tests/fixtures/diff/Cargo.toml".into(),
+            lock_path: lock_path.map(String::from),
+            ..Config::default()
+        })
+        .unwrap()
+    };
+    let old = graph(Some("tests/fixtures/diff/old.lock"));
+    let new = graph(None);
+    let pairs = |pairs: &[(String, String)]| {
+        pairs
+            .iter()
+            .map(|(a, b)| format!("{} {}", a, b))
+            .collect::<Vec<_>>()
+    };
+
+    let diff = new.diff(&old);
+    assert_eq!(pairs(&diff.added), ["getrandom 0.2.10", "itoa 1.0.0"]);
+    assert_eq!(pairs(&diff.removed), ["log 0.4.20"]);
+    assert_eq!(
+        diff.upgraded
+            .iter()
+            .map(|c| format!("{} {} {}", c.name, c.old, c.new))
+            .collect::<Vec<_>>(),
+        ["rand 0.7.3 0.8.5", "rand_core 0.5.1 0.6.4"]
+    );
+    assert_eq!(diff.downgraded[0].new, "1.0.100");
+    assert_eq!(
+        pairs(&diff.added_edges),
+        ["app itoa", "rand_core getrandom"]
+    );
+    assert_eq!(pairs(&diff.removed_edges), ["app log"]);
+    assert!(new.diff(&new).is_empty());
+
+    let write = |format| {
+        let mut out = vec![];
+        new.write_diff(&old, &diff, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert!(write(DiffFormat::Text).contains(
+        "\nUpgraded crates:\n  ~ rand v0.7.3 -> v0.8.5\n  ~ rand_core v0.5.1 -> v0.6.4\n"
+    ));
+    assert!(write(DiffFormat::Markdown).contains(
+        "| Downgraded | `serde` | 1.0.190 | 1.0.100 |\n\n**Added dependencies:**\n\n\
+         - `app -> itoa`\n"
+    ));
+    let dot = write(DiffFormat::Dot);
+    assert!(dot.contains("\tn2 [label=\"itoa\", color=green, fontcolor=green];\n"));
+    assert!(dot.contains("\tn3 [label=\"log\", color=red, fontcolor=red];\n"));
+    assert!(dot.contains("\tn4 [label=\"rand\\n0.7.3 -> 0.8.5\", color=orange];\n"));
+    assert!(dot.contains("\tn0 -> n3 [color=red, style=dashed];\n"));
+
+    // Projects can also be read from a git revision, which must exist.
+    assert!(get_dep_graph(Config {
+        rev: Some("no-such-revision".into()),
+        ..Config::default()
+    })
+    .is_err());
+}
+
+#[test]
+fn get_dep_graph_rev() {
+    // Commit the old and the new lock file to a scratch repository, then empty the working tree.
+    let dir = env::temp_dir().join(format!("cargo-deps-rev-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&dir)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "-q"]);
+    fs::copy("tests/fixtures/diff/Cargo.toml", dir.join("Cargo.toml")).unwrap();
+    fs::copy("tests/fixtures/diff/old.lock", dir.join("Cargo.lock")).unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "old"]);
+    fs::copy("tests/fixtures/diff/Cargo.lock", dir.join("Cargo.lock")).unwrap();
+    git(&["commit", "-q", "-a", "-m", "new"]);
+    fs::remove_file(dir.join("Cargo.toml")).unwrap();
+    fs::remove_file(dir.join("Cargo.lock")).unwrap();
+
+    let manifest_path = dir.join("Cargo.toml").to_str().unwrap().to_string();
+    let graph = |rev: &str| {
+        get_dep_graph(Config {
+            manifest_path: manifest_path.clone(),
+            rev: Some(rev.into()),
+            ..Config::default()
+        })
+    };
+    let old = graph("HEAD~1").unwrap();
+    let new = graph("HEAD").unwrap();
+    let diff = new.diff(&old);
+    assert_eq!(diff.removed[0], ("log".into(), "0.4.20".into()));
+    assert_eq!(diff.upgraded.len(), 2);
+
+    // Nothing is read from the working tree.
+    assert!(matches!(
+        get_dep_graph(Config {
+            manifest_path: manifest_path.clone(),
+            ..Config::default()
+        }),
+        Err(Error::ManifestNotFound(_))
+    ));
+    assert!(graph("no-such-revision").is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn render_dep_graph_invert() {
+    let cfg = Config {
+        invert: Some("unicode-width".into()),
+        ..Config::default()
+    };
+    let out = get_dep_graph(cfg).and_then(render_dep_graph).unwrap();
+    assert!(out.contains("\tn15 -> n14;\n"));
+    assert!(out.contains("\tn14 -> n9;\n"));
+    assert!(out.contains("\tn9 -> n8;\n"));
+    assert!(!out.contains("[label=\"toml\"]"));
+
+    let cfg = Config {
+        invert: Some("unicode-width".into()),
+        depth: Some(1),
+        ..Config::default()
+    };
+    let out = get_dep_graph(cfg).and_then(render_dep_graph).unwrap();
+    assert!(out.contains("\tn14 -> n9;\n"));
+    assert!(!out.contains("[label=\"cargo-deps\""));
+}
+
+#[test]
+fn render_dep_graph_focus_prune_exclude() {
+    let render = |cfg| get_dep_graph(cfg).and_then(render_dep_graph).unwrap();
+
+    let out = render(Config {
+        focus: Some(vec!["textwrap".into()]),
+        ..Config::default()
+    });
+    assert!(out.contains("\tn8 -> n9;\n"));
+    assert!(out.contains("\tn14 -> n15;\n"));
+    assert!(!out.contains("[label=\"toml\"]"));
+    assert!(!out.contains("[label=\"atty\"]"));
+
+    // Crates that are only reachable through clap are pruned along with it.
+    let out = render(Config {
+        prune: Some(vec!["clap".into()]),
+        ..Config::default()
+    });
+    assert!(out.contains("\tn8 -> n12;\n"));
+    assert!(!out.contains("[label=\"clap\"]"));
+    assert!(!out.contains("[label=\"textwrap\"]"));
+
+    // Pruning still starts from the root crates when the filter hides them.
+    let out = render(Config {
+        filter: Some(vec!["clap".into(), "textwrap".into(), "toml".into()]),
+        prune: Some(vec!["toml".into()]),
+        ..Config::default()
+    });
+    assert!(out.contains("[label=\"clap\"]"));
+    assert!(out.contains("[label=\"textwrap\"]"));
+    assert!(!out.contains("[label=\"toml\"]"));
+    assert!(!out.contains("[label=\"cargo-deps\""));
+
+    let out = render(Config {
+        exclude: Some(vec!["clap".into()]),
+        ..Config::default()
+    });
+    assert!(!out.contains("[label=\"clap\"]"));
+    assert!(out.contains("\tn14 -> n15;\n"));
+
+    let err = get_dep_graph(Config {
+        focus: Some(vec!["textwrap@9.9.9".into()]),
+        ..Config::default()
+    })
+    .err()
+    .unwrap();
+    assert_eq!(err.exit_code(), 2);
+}
+
+#[test]
+fn render_dep_graph_filter_contracts_paths() {
+    let cfg = Config {
+        filter: Some(vec![
+            "cargo-deps".into(),
+            "textwrap".into(),
+            "unicode-width".into(),
+            "libc".into(),
+        ]),
+        ..Config::default()
+    };
+    let out = get_dep_graph(cfg).and_then(render_dep_graph).unwrap();
+
+    assert!(out.contains("\tn8 -> n5 [style=dotted, label=\"via 3 crates\"];\n"));
+    assert!(out.contains("\tn8 -> n14 [style=dotted, label=\"via 1 crate\"];\n"));
+    assert!(out.contains("\tn14 -> n15;\n"));
+    assert!(!out.contains("[label=\"clap\"]"));
+}
+
+#[test]
+fn get_dep_graph_crate_selectors() {
+    let graph = get_dep_graph(Config::default()).unwrap();
+    let selected = |krate: &str| {
+        graph.paths_to(krate, 0).map(|paths| {
+            paths
+                .targets
+                .iter()
+                .map(|&n| graph.nodes[n].name.as_str())
+                .collect::<Vec<_>>()
+        })
+    };
+
+    assert_eq!(selected("unicode-*").unwrap(), ["unicode-width"]);
+    assert_eq!(
+        selected("re:^redox_").unwrap(),
+        ["redox_syscall", "redox_termios"]
+    );
+    assert_eq!(
+        selected("re:^(ansi|vec)_").unwrap(),
+        ["ansi_term", "vec_map"]
+    );
+    assert_eq!(
+        selected("winapi-i?86-*").unwrap(),
+        ["winapi-i686-pc-windows-gnu"]
+    );
+
+    // libc is at version 0.2.57.
+    for req in &[
+        "0.2",
+        "^0.2.50",
+        "~0.2.57",
+        "=0.2.57",
+        "<=0.2",
+        ">=0.2, <0.3",
+        "*",
+        "0.*",
+    ] {
+        assert!(selected(&format!("libc@{}", req)).is_ok(), "{}", req);
+    }
+    for req in &["0.3", "^0.2.58", ">0.2", "<0.2.57", "=0.2.5", "~0.1"] {
+        let err = selected(&format!("libc@{}", req)).err().unwrap();
+        assert_eq!(err.exit_code(), 2, "{}", req);
+    }
+
+    for krate in &["re:unicode-(width", "libc@abc", "@0.2"] {
+        let err = selected(krate).err().unwrap();
+        assert!(err.to_string().starts_with("Invalid crate"), "{}", krate);
+    }
+}
+
+#[test]
+fn render_dep_graph_clusters() {
+    let cfg = Config {
+        manifest_path: "tests/fixtures/clusters/Cargo.toml".into(),
+        clusters: vec![
+            Cluster::parse("async runtime=mio").unwrap(),
+            Cluster::parse("crypto=ring").unwrap(),
+        ],
+        ..Config::default()
+    };
+    let out = get_dep_graph(cfg).and_then(render_dep_graph).unwrap();
+
+    // Nodes: app (n0), ring (n1), serde_json (n2), tokio (n3), mio (n4), serde (n5).
+    assert!(out.contains(
+        "\tsubgraph cluster_0 {\n\t\tlabel=\"serialization\";\n\t\tcolor=darkgreen;\n\t\t\
+         style=dashed;\n\n\t\tn2 [label=\"serde_json\"];\n\t\tn5 [label=\"serde\"];\n\t}\n"
+    ));
+    // Clusters from the command line extend the ones from the manifest.
+    assert!(out.contains(
+        "\tsubgraph cluster_1 {\n\t\tlabel=\"async runtime\";\n\t\tcolor=darkcyan;\n\t\t\
+         style=dashed;\n\n\t\tn3 [label=\"tokio\"];\n\t\tn4 [label=\"mio\"];\n\t}\n"
+    ));
+    assert!(out.contains("\t\tlabel=\"crypto\";\n\t\tcolor=darkorange3;\n"));
+    assert!(out.contains("\tn3 -> n4;\n"));
+
+    assert_eq!(Cluster::parse("crypto").err().unwrap().exit_code(), 2);
+}
+
+#[test]
+fn render_dep_graph_cluster_by() {
+    let render = |cfg| get_dep_graph(cfg).and_then(render_dep_graph).unwrap();
+
+    let out = render(Config {
+        cluster_by: Some(ClusterBy::Prefix),
+        ..Config::default()
+    });
+    assert!(out.contains(
+        "\t\tlabel=\"redox*\";\n\t\tcolor=darkgreen;\n\t\tstyle=dashed;\n\n\t\t\
+         n18 [label=\"redox_syscall\"];\n\t\tn19 [label=\"redox_termios\"];\n\t}\n"
+    ));
+    assert!(out.contains("\t\tlabel=\"winapi*\";\n"));
+    // Crates without a family are not clustered.
+    assert!(!out.contains("\t\tlabel=\"clap*\";\n"));
+
+    // Named clusters come first.
+    let out = render(Config {
+        manifest_path: "tests/fixtures/sources/Cargo.toml".into(),
+        clusters: vec![Cluster::parse("local=local").unwrap()],
+        cluster_by: Some(ClusterBy::Source),
+        ..Config::default()
+    });
+    assert!(out.contains("\tsubgraph cluster_0 {\n\t\tlabel=\"local\";\n"));
+    assert!(out.contains("\tsubgraph cluster_1 {\n\t\tlabel=\"crates.io\";\n"));
+    assert!(out.contains("\tsubgraph cluster_3 {\n\t\tlabel=\"git\";\n"));
+    assert!(out.contains(
+        "\tsubgraph cluster_4 {\n\t\tlabel=\"path\";\n\t\tcolor=firebrick;\n\t\t\
+         style=dashed;\n\n\t\tn4 [label=\"src-root\", shape=box];\n\t}\n"
+    ));
+
+    let out = render(Config {
+        manifest_path: "tests/fixtures/workspace/Cargo.toml".into(),
+        cluster_by: Some(ClusterBy::Member),
+        ..Config::default()
+    });
+    assert!(out.contains("\t\tlabel=\"ws-cli\";\n"));
+    assert!(!out.contains("\t\tlabel=\"ws-a\";\n"));
+}